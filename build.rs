@@ -0,0 +1,14 @@
+fn main() {
+    println!("cargo:rerun-if-changed=c_version/schemes.c");
+    println!("cargo:rerun-if-changed=c_version/tagging.h");
+    // Always optimize, the C side only exists to be benchmarked
+    cc::Build::new()
+        .file("c_version/schemes.c")
+        .opt_level(3)
+        .compile("schemes");
+    // tests/roundtrip.rs compiles C for the same target
+    println!(
+        "cargo:rustc-env=TARGET={}",
+        std::env::var("TARGET").unwrap()
+    );
+}
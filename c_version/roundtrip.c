@@ -0,0 +1,73 @@
+// Round trips values through the Rust API in src/ffi.rs. `cargo test` builds
+// and runs it from tests/roundtrip.rs; by hand:
+//
+//   cargo build --release
+//   cc -O2 c_version/roundtrip.c target/release/libtagging.a -lpthread -ldl -lm -o target/roundtrip
+//   ./target/roundtrip
+#include <stdint.h>
+#include <stdio.h>
+
+#include "tagging.h"
+
+static int failures = 0;
+
+#define CHECK(scheme, ptr, tag)                                                \
+  do {                                                                         \
+    scheme##_t x = scheme##_make_tagged((ptr), (tag));                         \
+    if (scheme##_tag_of(x) != (tag) || scheme##_ptr_of(x) != (ptr)) {          \
+      printf(#scheme ": expected (%p, %d) got (%p, %d)\n", (ptr), (tag),       \
+             scheme##_ptr_of(x), scheme##_tag_of(x));                          \
+      failures++;                                                              \
+    }                                                                          \
+  } while (0)
+
+typedef LowBits low_bits_t;
+typedef LowByte low_byte_t;
+typedef HighBits high_bits_t;
+typedef HighByte high_byte_t;
+typedef NanBoxing nan_boxing_t;
+
+int main() {
+  static uint64_t objects[64];
+  for (int i = 0; i < 64; i++) {
+    const void *ptr = &objects[i];
+    for (uint8_t tag = 0; tag < 8; tag++) {
+      CHECK(low_bits, ptr, tag);
+      CHECK(low_byte, ptr, tag);
+      CHECK(high_bits, ptr, tag);
+      CHECK(high_byte, ptr, tag);
+      CHECK(nan_boxing, ptr, tag);
+    }
+  }
+
+  // Check the documented bit layouts
+  const void *ptr = (const void *)0x1000;
+  if ((uintptr_t)low_bits_make_tagged(ptr, 5).data != 0x1005) {
+    printf("low_bits: unexpected layout\n");
+    failures++;
+  }
+  if ((uintptr_t)high_byte_make_tagged(ptr, 5).data != 0x0500000000001000) {
+    printf("high_byte: unexpected layout\n");
+    failures++;
+  }
+  if ((uintptr_t)nan_boxing_make_tagged(ptr, 5).data != 0x7ffc000000001005) {
+    printf("nan_boxing: unexpected layout\n");
+    failures++;
+  }
+
+  // Any double that isn't a NaN is not a tagged pointer
+  union {
+    double d;
+    uint8_t *p;
+  } number = {.d = 1.5};
+  NanBoxing boxed = {number.p};
+  if (nan_boxing_tag_of(boxed) != 9) {
+    printf("nan_boxing: a double was decoded as a pointer\n");
+    failures++;
+  }
+
+  if (failures == 0) {
+    printf("all values round tripped\n");
+  }
+  return failures != 0;
+}
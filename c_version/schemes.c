@@ -0,0 +1,137 @@
+// Hand written C versions of the tagging schemes in src/types.rs, along with
+// some of the kernels from src/main.rs. These are linked into the benchmark
+// binary so the two languages can be compared on the same data.
+#include <stddef.h>
+#include <stdint.h>
+
+#include "tagging.h"
+
+typedef struct {
+  int32_t data;
+} Data;
+
+// `Basic` is `repr(u8)`, so it is laid out as a tag byte followed by the
+// pointer.
+typedef struct {
+  uint8_t tag;
+  Data *ptr;
+} BaseLine;
+
+#define NAN_BOXING_QNAN 0x7ffc000000000000ull
+#define NAN_BOXING_MASK (NAN_BOXING_QNAN | 0b111)
+
+static inline uint8_t baseline_tag(BaseLine self) { return self.tag; }
+
+static inline Data *baseline_data(BaseLine self) { return self.ptr; }
+
+static inline uint8_t low_bits_tag(LowBits self) {
+  return (uint8_t)((uint64_t)self.data & 0b111);
+}
+
+static inline Data *low_bits_data(LowBits self) {
+  return (Data *)((uint64_t)self.data - low_bits_tag(self));
+}
+
+static inline uint8_t low_byte_tag(LowByte self) {
+  return (uint8_t)(uint64_t)self.data;
+}
+
+static inline Data *low_byte_data(LowByte self) {
+  return (Data *)((uint64_t)self.data >> 8);
+}
+
+static inline uint8_t high_bits_tag(HighBits self) {
+  return (uint8_t)((uint64_t)self.data >> 61);
+}
+
+static inline Data *high_bits_data(HighBits self) {
+  return (Data *)((uint64_t)self.data << 3);
+}
+
+static inline uint8_t high_byte_tag(HighByte self) {
+  return (uint8_t)((uint64_t)self.data >> 56);
+}
+
+static inline Data *high_byte_data(HighByte self) {
+  return (Data *)((uint64_t)self.data & 0x00ffffffffffffffull);
+}
+
+static inline uint8_t nan_boxing_tag(NanBoxing self) {
+  if (((uint64_t)self.data & NAN_BOXING_QNAN) == NAN_BOXING_QNAN) {
+    return (uint8_t)((uint64_t)self.data & 0b111);
+  } else {
+    return 9; // invalid value
+  }
+}
+
+static inline Data *nan_boxing_data(NanBoxing self) {
+  return (Data *)((uint64_t)self.data & ~NAN_BOXING_MASK);
+}
+
+// Sums use unsigned arithmetic to get the wrapping behavior of the Rust
+// kernels without signed overflow.
+#define KERNELS(scheme, Scheme)                                                \
+  int32_t c_sum_t0_##scheme(const Scheme *x, size_t len) {                     \
+    uint32_t sum = 0;                                                          \
+    for (size_t i = 0; i < len; i++) {                                         \
+      if (scheme##_tag(x[i]) == 0) {                                           \
+        sum += (uint32_t)scheme##_data(x[i])->data;                            \
+      }                                                                        \
+    }                                                                          \
+    return (int32_t)sum;                                                       \
+  }                                                                            \
+                                                                               \
+  int32_t c_count_t1_##scheme(const Scheme *x, size_t len) {                   \
+    int32_t count = 0;                                                         \
+    for (size_t i = 0; i < len; i++) {                                         \
+      if (scheme##_tag(x[i]) == 1) {                                           \
+        count++;                                                               \
+      }                                                                        \
+    }                                                                          \
+    return count;                                                              \
+  }                                                                            \
+                                                                               \
+  int32_t c_sum_t0_t1_##scheme(const Scheme *x, size_t len) {                  \
+    uint32_t sum = 0;                                                          \
+    for (size_t i = 0; i < len; i++) {                                         \
+      switch (scheme##_tag(x[i])) {                                            \
+      case 0:                                                                  \
+      case 1:                                                                  \
+        sum += (uint32_t)scheme##_data(x[i])->data;                            \
+        break;                                                                 \
+      }                                                                        \
+    }                                                                          \
+    return (int32_t)sum;                                                       \
+  }                                                                            \
+                                                                               \
+  int32_t c_count_t1_t3_##scheme(const Scheme *x, size_t len) {                \
+    int32_t count = 0;                                                         \
+    for (size_t i = 0; i < len; i++) {                                         \
+      uint8_t tag = scheme##_tag(x[i]);                                        \
+      if (tag == 1 || tag == 3) {                                              \
+        count++;                                                               \
+      }                                                                        \
+    }                                                                          \
+    return count;                                                              \
+  }                                                                            \
+                                                                               \
+  int32_t c_sum_t1_t3_t5_##scheme(const Scheme *x, size_t len) {               \
+    uint32_t sum = 0;                                                          \
+    for (size_t i = 0; i < len; i++) {                                         \
+      switch (scheme##_tag(x[i])) {                                            \
+      case 1:                                                                  \
+      case 3:                                                                  \
+      case 5:                                                                  \
+        sum += (uint32_t)scheme##_data(x[i])->data;                            \
+        break;                                                                 \
+      }                                                                        \
+    }                                                                          \
+    return (int32_t)sum;                                                       \
+  }
+
+KERNELS(baseline, BaseLine)
+KERNELS(low_bits, LowBits)
+KERNELS(low_byte, LowByte)
+KERNELS(high_bits, HighBits)
+KERNELS(high_byte, HighByte)
+KERNELS(nan_boxing, NanBoxing)
@@ -0,0 +1,90 @@
+// C view of the tagging schemes in src/types.rs. Every scheme is a single
+// 64-bit word, declared here as a struct around a pointer so it is passed in
+// a register exactly like the `repr(transparent)` Rust types.
+//
+// The functions are implemented in src/ffi.rs and exported by the `tagging`
+// static library (`cargo build --release` produces target/release/libtagging.a).
+// `*_make_tagged` aborts if the pointer uses a reserved bit or the tag is
+// greater than 7.
+#ifndef TAGGING_H
+#define TAGGING_H
+
+#include <stdint.h>
+
+// LowBits: the tag is stored in the low 3 bits, which are always zero for an
+// 8 byte aligned pointer.
+//
+//   63                                              3 2   0
+//  +-------------------------------------------------+-----+
+//  |                 pointer[63:3]                   | tag |
+//  +-------------------------------------------------+-----+
+typedef struct {
+  uint8_t *data;
+} LowBits;
+
+// LowByte: the pointer is shifted up by 8 and the tag takes the low byte.
+// Pointers must fit in 56 bits.
+//
+//   63                                          8 7        0
+//  +---------------------------------------------+----------+
+//  |                pointer[55:0]                |   tag    |
+//  +---------------------------------------------+----------+
+typedef struct {
+  uint8_t *data;
+} LowByte;
+
+// HighBits: the pointer is shifted down by 3 (it is 8 byte aligned) and the
+// tag takes the top 3 bits. Pointers must fit in 61 bits.
+//
+//   63 61 60                                               0
+//  +-----+--------------------------------------------------+
+//  | tag |                 pointer[60:3]                    |
+//  +-----+--------------------------------------------------+
+typedef struct {
+  uint8_t *data;
+} HighBits;
+
+// HighByte: the tag takes the top byte and the pointer is stored as is.
+// Pointers must fit in 56 bits.
+//
+//   63      56 55                                          0
+//  +----------+---------------------------------------------+
+//  |   tag    |                pointer[55:0]                |
+//  +----------+---------------------------------------------+
+typedef struct {
+  uint8_t *data;
+} HighByte;
+
+// NanBoxing: bits 50 to 62 are all set, making the word a quiet NaN when read
+// as a double. The tag takes the low 3 bits. Pointers must be 8 byte aligned
+// and fit in 50 bits. Any word that isn't such a NaN has tag 9.
+//
+//   63 62           50 49                              3 2   0
+//  +--+---------------+---------------------------------+-----+
+//  |0 | 1111111111111 |          pointer[49:3]          | tag |
+//  +--+---------------+---------------------------------+-----+
+typedef struct {
+  uint8_t *data;
+} NanBoxing;
+
+LowBits low_bits_make_tagged(const void *ptr, uint8_t tag);
+uint8_t low_bits_tag_of(LowBits x);
+const void *low_bits_ptr_of(LowBits x);
+
+LowByte low_byte_make_tagged(const void *ptr, uint8_t tag);
+uint8_t low_byte_tag_of(LowByte x);
+const void *low_byte_ptr_of(LowByte x);
+
+HighBits high_bits_make_tagged(const void *ptr, uint8_t tag);
+uint8_t high_bits_tag_of(HighBits x);
+const void *high_bits_ptr_of(HighBits x);
+
+HighByte high_byte_make_tagged(const void *ptr, uint8_t tag);
+uint8_t high_byte_tag_of(HighByte x);
+const void *high_byte_ptr_of(HighByte x);
+
+NanBoxing nan_boxing_make_tagged(const void *ptr, uint8_t tag);
+uint8_t nan_boxing_tag_of(NanBoxing x);
+const void *nan_boxing_ptr_of(NanBoxing x);
+
+#endif
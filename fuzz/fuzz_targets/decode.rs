@@ -0,0 +1,26 @@
+// Decode arbitrary words with every scheme. Anything `try_untag` accepts has to
+// be safe to use and encode back to the same word.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tagging::types::*;
+
+fn decode<S: FromBits<Basic>>(bits: usize) {
+    if let Ok(basic) = S::try_untag(bits) {
+        let x = S::new(basic);
+        assert_eq!(x.to_bits(), bits);
+        assert_eq!((x.tag(), x.data()), (basic.tag(), basic.ptr()));
+    }
+}
+
+fuzz_target!(|input: (usize, u8)| {
+    let (bits, tag) = input;
+    decode::<LowBits<Basic>>(bits);
+    decode::<LowByte<Basic>>(bits);
+    decode::<HighBits<Basic>>(bits);
+    decode::<HighByte<Basic>>(bits);
+    decode::<NanBoxing<Basic>>(bits);
+    if let Ok(basic) = Basic::try_from_raw(bits as *const u8, tag) {
+        assert_eq!(BaseLine::new(basic).untag(), basic);
+    }
+});
@@ -0,0 +1,394 @@
+// Disassemble the probes in the running binary and compare them across
+// schemes, so codegen changes show up without a manual `cargo asm` session.
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use crate::probes::{symbol, PROBES, SCHEMES};
+
+const REPORT_DIR: &str = "target/asm";
+
+/// Disassemble `exe` and return the instructions of every function, keyed by
+/// symbol name. Alignment padding is dropped.
+pub fn disassemble(exe: &Path) -> io::Result<HashMap<String, Vec<String>>> {
+    let output = Command::new("objdump")
+        .args(["-d", "--no-show-raw-insn", "--no-addresses", "-M", "intel"])
+        .arg(exe)
+        .output()?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(io::Error::other(format!("objdump failed: {stderr}")));
+    }
+
+    let mut functions = HashMap::new();
+    let mut current: Option<(String, Vec<String>)> = None;
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        // Function headers look like `<name>:`
+        if let Some(name) = line.strip_prefix('<').and_then(|x| x.strip_suffix(">:")) {
+            if let Some((name, body)) = current.take() {
+                functions.insert(name, body);
+            }
+            current = Some((name.to_string(), Vec::new()));
+        } else if let Some((_, body)) = &mut current {
+            let insn = line.trim();
+            if insn.is_empty() || is_padding(insn) {
+                continue;
+            }
+            body.push(insn.split_whitespace().collect::<Vec<_>>().join(" "));
+        }
+    }
+    if let Some((name, body)) = current {
+        functions.insert(name, body);
+    }
+    Ok(functions)
+}
+
+fn is_padding(insn: &str) -> bool {
+    insn.starts_with("nop")
+        || insn.starts_with("int3")
+        || insn.starts_with("cs nop")
+        || insn.starts_with("xchg ax,ax")
+}
+
+/// A probe and its instructions for each of `SCHEMES`.
+pub type Listing = (&'static str, Vec<Vec<String>>);
+
+/// The instructions of every probe for every scheme, in `PROBES` order.
+pub fn probe_listings(exe: &Path) -> io::Result<Vec<Listing>> {
+    let mut functions = disassemble(exe)?;
+    let listings = PROBES
+        .iter()
+        .map(|probe| {
+            let per_scheme = SCHEMES
+                .iter()
+                .map(|scheme| functions.remove(&symbol(probe, scheme)).unwrap_or_default())
+                .collect();
+            (*probe, per_scheme)
+        })
+        .collect();
+    Ok(listings)
+}
+
+/// Print a table of instruction counts and write a side by side listing of
+/// every probe to `target/asm/probes.md`.
+pub fn report() -> io::Result<()> {
+    if cfg!(debug_assertions) {
+        eprintln!("warning: this is a debug build, run with --release for meaningful assembly");
+    }
+    let exe = std::env::current_exe()?;
+    let listings = probe_listings(&exe)?;
+
+    let mut out = String::new();
+    writeln!(out, "# Probe instruction counts\n").unwrap();
+    write!(out, "| probe |").unwrap();
+    for scheme in SCHEMES {
+        write!(out, " {scheme} |").unwrap();
+    }
+    write!(out, "\n|---|").unwrap();
+    for _ in SCHEMES {
+        write!(out, "---:|").unwrap();
+    }
+    writeln!(out).unwrap();
+    for (probe, per_scheme) in &listings {
+        write!(out, "| {probe} |").unwrap();
+        for insns in per_scheme {
+            if insns.is_empty() {
+                write!(out, " - |").unwrap();
+            } else {
+                write!(out, " {} |", insns.len()).unwrap();
+            }
+        }
+        writeln!(out).unwrap();
+    }
+    print!("{out}");
+
+    for (probe, per_scheme) in &listings {
+        writeln!(out, "\n## {probe}\n\n```text").unwrap();
+        let widths: Vec<usize> = SCHEMES
+            .iter()
+            .zip(per_scheme)
+            .map(|(scheme, insns)| {
+                insns
+                    .iter()
+                    .map(|x| x.len())
+                    .chain([scheme.len()])
+                    .max()
+                    .unwrap()
+            })
+            .collect();
+        let rows = per_scheme.iter().map(|x| x.len()).max().unwrap_or(0);
+        let line = |cells: Vec<&str>| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{cell:width$}"))
+                .collect();
+            cells.join(" | ").trim_end().to_string()
+        };
+        let rules: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+        writeln!(out, "{}", line(SCHEMES.to_vec())).unwrap();
+        writeln!(out, "{}", line(rules.iter().map(|x| x.as_str()).collect())).unwrap();
+        for row in 0..rows {
+            let cells = per_scheme
+                .iter()
+                .map(|insns| insns.get(row).map_or("", |x| x.as_str()))
+                .collect();
+            writeln!(out, "{}", line(cells)).unwrap();
+        }
+        writeln!(out, "```").unwrap();
+    }
+
+    std::fs::create_dir_all(REPORT_DIR)?;
+    let path = Path::new(REPORT_DIR).join("probes.md");
+    std::fs::write(&path, out)?;
+    println!("\nlistings written to {}", path.display());
+    Ok(())
+}
+
+// The patterns below are x86-64 release codegen, so the tests only exist in
+// optimized builds: `cargo test --release asm::`.
+#[cfg(all(test, debug_assertions))]
+#[test]
+#[ignore = "the codegen tests check release builds, run `cargo test --release asm::`"]
+fn codegen_tests_need_release() {}
+
+#[cfg(all(test, target_arch = "x86_64", not(debug_assertions)))]
+mod tests {
+    use std::sync::OnceLock;
+
+    use super::*;
+
+    fn functions() -> &'static HashMap<String, Vec<String>> {
+        static FUNCTIONS: OnceLock<HashMap<String, Vec<String>>> = OnceLock::new();
+        FUNCTIONS.get_or_init(|| {
+            crate::probes::run();
+            let exe = std::env::current_exe().unwrap();
+            disassemble(&exe).unwrap_or_else(|e| {
+                panic!("failed to disassemble the test binary ({e}), install binutils or set TAGGING_SKIP_CODEGEN=1")
+            })
+        })
+    }
+
+    /// Whether `TAGGING_SKIP_CODEGEN` opts out of the codegen tests, e.g. on a
+    /// machine without objdump. A missing objdump fails them otherwise.
+    fn skip() -> bool {
+        let skip = std::env::var_os("TAGGING_SKIP_CODEGEN").is_some();
+        if skip {
+            eprintln!("skipping: TAGGING_SKIP_CODEGEN is set");
+        }
+        skip
+    }
+
+    fn listing(probe: &str, scheme: &str) -> &'static [String] {
+        let symbol = symbol(probe, scheme);
+        functions()
+            .get(&symbol)
+            .unwrap_or_else(|| panic!("{symbol} not found in the disassembly"))
+    }
+
+    /// The instructions of a probe, or of the function it tail calls when
+    /// LLVM didn't inline the kernel into it.
+    fn body(probe: &str, scheme: &str) -> &'static [String] {
+        let insns = listing(probe, scheme);
+        let target = match insns {
+            [jmp] => jmp
+                .split_once('<')
+                .and_then(|(_, target)| target.strip_suffix('>')),
+            _ => None,
+        };
+        target
+            .and_then(|target| functions().get(target))
+            .map_or(insns, |x| x)
+    }
+
+    /// FileCheck style: every pattern has to match an instruction, in order.
+    #[track_caller]
+    fn check(insns: &[String], patterns: &[&str]) {
+        let mut rest = insns.iter();
+        for pattern in patterns {
+            assert!(
+                rest.any(|insn| insn.contains(pattern)),
+                "`{pattern}` not found in order in:\n{}",
+                insns.join("\n")
+            );
+        }
+    }
+
+    #[track_caller]
+    fn check_not(insns: &[String], pattern: &str) {
+        if let Some(insn) = insns.iter().find(|insn| insn.contains(pattern)) {
+            panic!("unexpected `{insn}` in:\n{}", insns.join("\n"));
+        }
+    }
+
+    fn branches(insns: &[String]) -> usize {
+        insns
+            .iter()
+            .filter(|insn| insn.starts_with('j') && !insn.starts_with("jmp"))
+            .count()
+    }
+
+    #[test]
+    fn low_bits_known_tag_folds_into_load() {
+        if skip() {
+            return;
+        }
+        // Subtracting the tag becomes the displacement of the load
+        let insns = listing("untag_bit1", "low_bits");
+        check(insns, &["cmp", "mov eax,DWORD PTR [rdi-0x1]", "ret"]);
+        check_not(insns, "sub");
+    }
+
+    #[test]
+    fn high_byte_tag_check_is_shift_and_compare() {
+        if skip() {
+            return;
+        }
+        let insns = listing("check_bit0", "high_byte");
+        check(insns, &["shr", "set", "ret"]);
+        assert_eq!(insns.len(), 3, "{insns:#?}");
+    }
+
+    #[test]
+    fn high_bits_tag_check_is_shift_and_compare() {
+        if skip() {
+            return;
+        }
+        let insns = listing("check_bit0", "high_bits");
+        check(insns, &["shr", "set", "ret"]);
+        assert_eq!(insns.len(), 3, "{insns:#?}");
+    }
+
+    #[test]
+    fn elide_hint_removes_tag_check() {
+        if skip() {
+            return;
+        }
+        // HighByte is left out, its hint doesn't let LLVM drop the check
+        for scheme in [
+            "baseline",
+            "low_bits",
+            "low_byte",
+            "high_bits",
+            "nan_boxing",
+        ] {
+            let insns = listing("elide", scheme);
+            assert_eq!(branches(insns), 0, "{scheme}: {insns:#?}");
+            check(insns, &["DWORD PTR", "ret"]);
+        }
+    }
+
+    #[test]
+    fn untag_enum_matches_raw_tag_dispatch() {
+        if skip() {
+            return;
+        }
+        // Matching on `untag()` should be no worse than matching on `tag()`
+        // for the packed schemes
+        for scheme in ["low_bits", "low_byte", "high_bits", "high_byte"] {
+            let from_enum = listing("untag_enum_2", scheme).len();
+            let from_tag = listing("untag_raw_2", scheme).len();
+            assert!(from_enum <= from_tag, "{scheme}: {from_enum} > {from_tag}");
+        }
+    }
+
+    #[test]
+    fn dispatch_kernels_keep_their_shape() {
+        if skip() {
+            return;
+        }
+        // Jumps to labels in the same function print the label
+        let indirect = |insns: &[String]| {
+            insns
+                .iter()
+                .any(|insn| insn.starts_with("jmp") && !insn.contains('<'))
+        };
+        for scheme in SCHEMES {
+            let insns = body("dispatch_jump_table", scheme);
+            assert!(indirect(insns), "{scheme}: no indirect jump in {insns:#?}");
+
+            // A compare and branch for each of the first seven variants
+            let insns = body("dispatch_branch_chain", scheme);
+            let compares = insns.iter().filter(|insn| insn.starts_with("cmp")).count();
+            assert!(!indirect(insns), "{scheme}: {insns:#?}");
+            assert!(
+                compares >= 7 && branches(insns) >= 7,
+                "{scheme}: {insns:#?}"
+            );
+
+            // Nothing but the loop: the empty check and the back edge
+            let insns = body("dispatch_select", scheme);
+            assert!(!indirect(insns), "{scheme}: {insns:#?}");
+            assert!(branches(insns) <= 2, "{scheme}: {insns:#?}");
+        }
+    }
+
+    #[test]
+    fn arguments_spill_after_six_words() {
+        if skip() {
+            return;
+        }
+        // SysV passes six words in registers: three `BaseLine`s or six of any
+        // single word scheme
+        let on_stack = |probe, scheme| {
+            listing(probe, scheme)
+                .iter()
+                .any(|insn| insn.contains("[rsp"))
+        };
+        assert!(
+            !on_stack("callee_3", "baseline"),
+            "{:#?}",
+            listing("callee_3", "baseline")
+        );
+        assert!(
+            on_stack("callee_4", "baseline"),
+            "{:#?}",
+            listing("callee_4", "baseline")
+        );
+        for scheme in &SCHEMES[1..] {
+            assert!(
+                !on_stack("callee_6", scheme),
+                "{scheme}: {:#?}",
+                listing("callee_6", scheme)
+            );
+            assert!(
+                on_stack("callee_7", scheme),
+                "{scheme}: {:#?}",
+                listing("callee_7", scheme)
+            );
+        }
+    }
+
+    // Upper bounds on the number of instructions, in `SCHEMES` order. These
+    // have a little slack so that unrelated scheduling changes don't fail.
+    const CEILINGS: &[(&str, [usize; 6])] = &[
+        ("untag_bit0", [7, 7, 8, 7, 9, 11]),
+        ("untag_bit1", [6, 8, 7, 8, 10, 11]),
+        ("check_bit0", [3, 3, 3, 3, 3, 7]),
+        ("count_t0", [45, 60, 60, 60, 55, 65]),
+        ("untag_enum_2", [65, 26, 26, 26, 28, 42]),
+        ("untag_raw_2", [68, 70, 70, 65, 70, 42]),
+        ("sum_t0", [48, 50, 52, 50, 55, 58]),
+        ("sum_repr", [92, 78, 100, 66, 72, 100]),
+        ("elide", [2, 2, 4, 3, 9, 2]),
+    ];
+
+    #[test]
+    fn instruction_count_ceilings() {
+        if skip() {
+            return;
+        }
+        let mut failures = Vec::new();
+        for (probe, ceilings) in CEILINGS {
+            for (scheme, &ceiling) in SCHEMES.iter().zip(ceilings) {
+                let count = listing(probe, scheme).len();
+                if count > ceiling {
+                    failures.push(format!("{probe}/{scheme}: {count} > {ceiling}"));
+                }
+            }
+        }
+        assert!(failures.is_empty(), "{}", failures.join("\n"));
+    }
+}
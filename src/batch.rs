@@ -0,0 +1,476 @@
+// Batch operations on slices of tagged values. The single word schemes decode
+// tags with SSE2 and count or filter them with SSE2, or AVX2 when the CPU has
+// it; everything else falls back to a scalar loop.
+use crate::types::*;
+
+/// The tag returned for words that aren't valid tagged values, matching
+/// `NanBoxing::tag`.
+const INVALID: u8 = 9;
+
+/// Where a single word scheme keeps its tag. The tag is
+/// `(word >> SHIFT) & MASK`, provided `word & VALID_MASK == VALID_BITS`.
+///
+/// # Safety
+/// `Self` has to be `repr(transparent)` over a pointer.
+pub unsafe trait WordLayout: Copy {
+    const SHIFT: u32;
+    const MASK: u64;
+    const VALID_MASK: u64 = 0;
+    const VALID_BITS: u64 = 0;
+}
+
+unsafe impl<T: Copy> WordLayout for LowBits<T> {
+    const SHIFT: u32 = 0;
+    const MASK: u64 = 0b111;
+}
+
+unsafe impl<T: Copy> WordLayout for LowByte<T> {
+    const SHIFT: u32 = 0;
+    const MASK: u64 = 0xFF;
+}
+
+unsafe impl<T: Copy> WordLayout for HighBits<T> {
+    const SHIFT: u32 = 61;
+    const MASK: u64 = 0b111;
+}
+
+unsafe impl<T: Copy> WordLayout for HighByte<T> {
+    const SHIFT: u32 = 56;
+    const MASK: u64 = 0xFF;
+}
+
+unsafe impl<T: Copy> WordLayout for NanBoxing<T> {
+    const SHIFT: u32 = 0;
+    const MASK: u64 = 0b111;
+    const VALID_MASK: u64 = 0x7ffc000000000000;
+    const VALID_BITS: u64 = 0x7ffc000000000000;
+}
+
+pub trait BatchTags: Sized + Copy {
+    /// Write the tag of every element of `x` to `out`, which must be at least
+    /// as long.
+    fn tags_into(x: &[Self], out: &mut [u8]);
+    fn count_tag(x: &[Self], tag: u8) -> usize;
+    /// Append the elements of `x` with tag `tag` to `out`, keeping their order.
+    fn filter_by_tag(x: &[Self], tag: u8, out: &mut Vec<Self>);
+}
+
+impl BatchTags for BaseLine {
+    fn tags_into(x: &[Self], out: &mut [u8]) {
+        for (x, out) in x.iter().zip(out) {
+            *out = x.tag();
+        }
+    }
+
+    fn count_tag(x: &[Self], tag: u8) -> usize {
+        x.iter().filter(|x| x.tag() == tag).count()
+    }
+
+    fn filter_by_tag(x: &[Self], tag: u8, out: &mut Vec<Self>) {
+        out.extend(x.iter().filter(|x| x.tag() == tag));
+    }
+}
+
+macro_rules! word_batch_tags {
+    ($($scheme:ident),*) => {
+        $(
+            impl<T: Taggable + Copy> BatchTags for $scheme<T> {
+                fn tags_into(x: &[Self], out: &mut [u8]) {
+                    words::tags_into::<Self>(x, out)
+                }
+
+                fn count_tag(x: &[Self], tag: u8) -> usize {
+                    words::count_tag::<Self>(x, tag)
+                }
+
+                fn filter_by_tag(x: &[Self], tag: u8, out: &mut Vec<Self>) {
+                    words::filter_by_tag::<Self>(x, tag, out)
+                }
+            }
+        )*
+    };
+}
+
+word_batch_tags!(LowBits, LowByte, HighBits, HighByte, NanBoxing);
+
+pub fn tags_of<S: BatchTags, const N: usize>(x: &[S; N]) -> [u8; N] {
+    let mut tags = [0; N];
+    S::tags_into(x, &mut tags);
+    tags
+}
+
+pub fn count_tag<S: BatchTags>(x: &[S], tag: u8) -> usize {
+    S::count_tag(x, tag)
+}
+
+pub fn filter_by_tag<S: BatchTags>(x: &[S], tag: u8) -> Vec<S> {
+    let mut out = Vec::new();
+    S::filter_by_tag(x, tag, &mut out);
+    out
+}
+
+mod words {
+    use super::{WordLayout, INVALID};
+
+    fn as_words<S: WordLayout>(x: &[S]) -> &[u64] {
+        // SAFETY: `WordLayout` guarantees S has the layout of a pointer
+        unsafe { std::slice::from_raw_parts(x.as_ptr().cast::<u64>(), x.len()) }
+    }
+
+    #[inline(always)]
+    fn tag<S: WordLayout>(word: u64) -> u8 {
+        if word & S::VALID_MASK == S::VALID_BITS {
+            ((word >> S::SHIFT) & S::MASK) as u8
+        } else {
+            INVALID
+        }
+    }
+
+    /// A word has tag `tag` iff `word & mask == bits`. `None` when `tag`
+    /// can't be encoded, in which case only the scalar path can find it.
+    #[inline(always)]
+    fn matcher<S: WordLayout>(tag: u8) -> Option<(u64, u64)> {
+        (tag as u64 <= S::MASK).then(|| {
+            (
+                S::VALID_MASK | S::MASK << S::SHIFT,
+                S::VALID_BITS | (tag as u64) << S::SHIFT,
+            )
+        })
+    }
+
+    pub fn tags_into<S: WordLayout>(x: &[S], out: &mut [u8]) {
+        let words = as_words(x);
+        let out = &mut out[..words.len()];
+        #[cfg(target_arch = "x86_64")]
+        let done = unsafe { x86::tags_into_sse2::<S>(words, out) };
+        #[cfg(not(target_arch = "x86_64"))]
+        let done = 0;
+        for (word, out) in words[done..].iter().zip(&mut out[done..]) {
+            *out = tag::<S>(*word);
+        }
+    }
+
+    pub fn count_tag<S: WordLayout>(x: &[S], tag: u8) -> usize {
+        let words = as_words(x);
+        let Some((mask, bits)) = matcher::<S>(tag) else {
+            return words.iter().filter(|&&w| self::tag::<S>(w) == tag).count();
+        };
+        #[cfg(target_arch = "x86_64")]
+        let (done, count) = unsafe {
+            if is_x86_feature_detected!("avx2") {
+                x86::count_avx2(words, mask, bits)
+            } else {
+                x86::count_sse2(words, mask, bits)
+            }
+        };
+        #[cfg(not(target_arch = "x86_64"))]
+        let (done, count) = (0, 0);
+        count + words[done..].iter().filter(|&&w| w & mask == bits).count()
+    }
+
+    pub fn filter_by_tag<S: WordLayout>(x: &[S], tag: u8, out: &mut Vec<S>) {
+        let words = as_words(x);
+        let Some((mask, bits)) = matcher::<S>(tag) else {
+            out.extend(
+                x.iter()
+                    .zip(words)
+                    .filter(|(_, &w)| self::tag::<S>(w) == tag)
+                    .map(|(x, _)| *x),
+            );
+            return;
+        };
+        #[cfg(target_arch = "x86_64")]
+        let done = unsafe {
+            if is_x86_feature_detected!("avx2") {
+                x86::filter_avx2(x, words, mask, bits, out)
+            } else {
+                x86::filter_sse2(x, words, mask, bits, out)
+            }
+        };
+        #[cfg(not(target_arch = "x86_64"))]
+        let done = 0;
+        out.extend(
+            x[done..]
+                .iter()
+                .zip(&words[done..])
+                .filter(|(_, &w)| w & mask == bits)
+                .map(|(x, _)| *x),
+        );
+    }
+
+    // Each function returns how many leading words it handled, the rest are
+    // left to the scalar loop.
+    #[cfg(target_arch = "x86_64")]
+    mod x86 {
+        use std::arch::x86_64::*;
+
+        use super::{WordLayout, INVALID};
+
+        /// SSE2 has no 64 bit compare, so compare the halves and combine.
+        #[inline(always)]
+        unsafe fn cmpeq_epi64_sse2(a: __m128i, b: __m128i) -> __m128i {
+            let eq = _mm_cmpeq_epi32(a, b);
+            _mm_and_si128(eq, _mm_shuffle_epi32::<0b10_11_00_01>(eq))
+        }
+
+        #[inline(always)]
+        unsafe fn tags_sse2<S: WordLayout>(words: __m128i) -> __m128i {
+            let shift = _mm_cvtsi32_si128(S::SHIFT as i32);
+            let tags = _mm_and_si128(_mm_srl_epi64(words, shift), _mm_set1_epi64x(S::MASK as i64));
+            if S::VALID_MASK == 0 {
+                return tags;
+            }
+            let valid_bits = _mm_and_si128(words, _mm_set1_epi64x(S::VALID_MASK as i64));
+            let valid = cmpeq_epi64_sse2(valid_bits, _mm_set1_epi64x(S::VALID_BITS as i64));
+            _mm_or_si128(
+                _mm_and_si128(valid, tags),
+                _mm_andnot_si128(valid, _mm_set1_epi64x(INVALID as i64)),
+            )
+        }
+
+        pub unsafe fn tags_into_sse2<S: WordLayout>(words: &[u64], out: &mut [u8]) -> usize {
+            let chunks = words.len() / 8;
+            for i in 0..chunks {
+                let src = words.as_ptr().add(i * 8).cast::<__m128i>();
+                let a = tags_sse2::<S>(_mm_loadu_si128(src));
+                let b = tags_sse2::<S>(_mm_loadu_si128(src.add(1)));
+                let c = tags_sse2::<S>(_mm_loadu_si128(src.add(2)));
+                let d = tags_sse2::<S>(_mm_loadu_si128(src.add(3)));
+                // Every lane is < 256, so gather the low dwords and narrow twice
+                let ab = _mm_castps_si128(_mm_shuffle_ps::<0b10_00_10_00>(
+                    _mm_castsi128_ps(a),
+                    _mm_castsi128_ps(b),
+                ));
+                let cd = _mm_castps_si128(_mm_shuffle_ps::<0b10_00_10_00>(
+                    _mm_castsi128_ps(c),
+                    _mm_castsi128_ps(d),
+                ));
+                let halves = _mm_packs_epi32(ab, cd);
+                let bytes = _mm_packus_epi16(halves, halves);
+                _mm_storel_epi64(out.as_mut_ptr().add(i * 8).cast::<__m128i>(), bytes);
+            }
+            chunks * 8
+        }
+
+        pub unsafe fn count_sse2(words: &[u64], mask: u64, bits: u64) -> (usize, usize) {
+            let mask = _mm_set1_epi64x(mask as i64);
+            let bits = _mm_set1_epi64x(bits as i64);
+            let mut counts = _mm_setzero_si128();
+            let chunks = words.len() / 2;
+            for i in 0..chunks {
+                let x = _mm_loadu_si128(words.as_ptr().add(i * 2).cast());
+                // Matching lanes are all ones, i.e. -1
+                counts = _mm_sub_epi64(counts, cmpeq_epi64_sse2(_mm_and_si128(x, mask), bits));
+            }
+            let mut lanes = [0u64; 2];
+            _mm_storeu_si128(lanes.as_mut_ptr().cast(), counts);
+            (chunks * 2, (lanes[0] + lanes[1]) as usize)
+        }
+
+        #[target_feature(enable = "avx2")]
+        pub unsafe fn count_avx2(words: &[u64], mask: u64, bits: u64) -> (usize, usize) {
+            let mask = _mm256_set1_epi64x(mask as i64);
+            let bits = _mm256_set1_epi64x(bits as i64);
+            let mut counts = _mm256_setzero_si256();
+            let chunks = words.len() / 4;
+            for i in 0..chunks {
+                let x = _mm256_loadu_si256(words.as_ptr().add(i * 4).cast());
+                counts =
+                    _mm256_sub_epi64(counts, _mm256_cmpeq_epi64(_mm256_and_si256(x, mask), bits));
+            }
+            let mut lanes = [0u64; 4];
+            _mm256_storeu_si256(lanes.as_mut_ptr().cast(), counts);
+            (chunks * 4, lanes.iter().sum::<u64>() as usize)
+        }
+
+        pub unsafe fn filter_sse2<S: Copy>(
+            x: &[S],
+            words: &[u64],
+            mask: u64,
+            bits: u64,
+            out: &mut Vec<S>,
+        ) -> usize {
+            let mask = _mm_set1_epi64x(mask as i64);
+            let bits = _mm_set1_epi64x(bits as i64);
+            let chunks = words.len() / 2;
+            for i in 0..chunks {
+                let w = _mm_loadu_si128(words.as_ptr().add(i * 2).cast());
+                let eq = cmpeq_epi64_sse2(_mm_and_si128(w, mask), bits);
+                let mut hits = _mm_movemask_pd(_mm_castsi128_pd(eq));
+                while hits != 0 {
+                    out.push(*x.get_unchecked(i * 2 + hits.trailing_zeros() as usize));
+                    hits &= hits - 1;
+                }
+            }
+            chunks * 2
+        }
+
+        #[target_feature(enable = "avx2")]
+        pub unsafe fn filter_avx2<S: Copy>(
+            x: &[S],
+            words: &[u64],
+            mask: u64,
+            bits: u64,
+            out: &mut Vec<S>,
+        ) -> usize {
+            let mask = _mm256_set1_epi64x(mask as i64);
+            let bits = _mm256_set1_epi64x(bits as i64);
+            let chunks = words.len() / 4;
+            for i in 0..chunks {
+                let w = _mm256_loadu_si256(words.as_ptr().add(i * 4).cast());
+                let eq = _mm256_cmpeq_epi64(_mm256_and_si256(w, mask), bits);
+                let mut hits = _mm256_movemask_pd(_mm256_castsi256_pd(eq));
+                while hits != 0 {
+                    out.push(*x.get_unchecked(i * 4 + hits.trailing_zeros() as usize));
+                    hits &= hits - 1;
+                }
+            }
+            chunks * 4
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// Reinterpret arbitrary words, including ones `from_raw` never produces.
+    fn from_words<S: WordLayout>(words: &[u64]) -> Vec<S> {
+        // SAFETY: `WordLayout` guarantees S has the layout of a pointer
+        words
+            .iter()
+            .map(|word| unsafe { std::mem::transmute_copy::<u64, S>(word) })
+            .collect()
+    }
+
+    fn to_words<S: WordLayout>(x: &[S]) -> Vec<u64> {
+        x.iter()
+            .map(|x| unsafe { std::mem::transmute_copy::<S, u64>(x) })
+            .collect()
+    }
+
+    /// A valid word with the low bits of `tag` in the tag field.
+    fn tagged<S: WordLayout>(payload: u64, tag: u8) -> u64 {
+        payload & !(S::MASK << S::SHIFT) & !S::VALID_MASK
+            | S::VALID_BITS
+            | (tag as u64 & S::MASK) << S::SHIFT
+    }
+
+    fn word<S: WordLayout>() -> impl Strategy<Value = u64> {
+        prop_oneof![
+            any::<u64>(),
+            (any::<u64>(), any::<u8>()).prop_map(|(payload, tag)| tagged::<S>(payload, tag)),
+            // Valid apart from one bit
+            (any::<u64>(), any::<u8>(), 0..64u32)
+                .prop_map(
+                    |(payload, tag, bit)| tagged::<S>(payload, tag) ^ (S::VALID_MASK & 1 << bit)
+                ),
+        ]
+    }
+
+    /// `len` words cycling through valid tags, tags too large for a 3 bit
+    /// scheme, doubles and almost valid words.
+    fn mixed_words<S: WordLayout>(len: usize) -> Vec<u64> {
+        (0..len as u64)
+            .map(|i| {
+                let payload = i.wrapping_mul(0x9e37_79b9_7f4a_7c15);
+                match i % 4 {
+                    0 => tagged::<S>(payload, i as u8 % 8),
+                    1 => tagged::<S>(payload, (i * 37) as u8),
+                    2 => (i as f64 + 0.5).to_bits(),
+                    _ => tagged::<S>(payload, 3) ^ (S::VALID_MASK & 1 << 52),
+                }
+            })
+            .collect()
+    }
+
+    /// The batch operations agree with `tag` on every element.
+    fn check<S: BatchTags + WordLayout + TaggedPointer<Basic>>(words: &[u64]) {
+        let x: Vec<S> = from_words(words);
+        let tags: Vec<u8> = x.iter().map(|x| x.tag()).collect();
+        let mut out = vec![0; x.len()];
+        S::tags_into(&x, &mut out);
+        assert_eq!(out, tags, "tags_into of {words:#x?}");
+        for tag in 0..=255 {
+            let expected: Vec<u64> = words
+                .iter()
+                .zip(&tags)
+                .filter(|(_, &t)| t == tag)
+                .map(|(&word, _)| word)
+                .collect();
+            assert_eq!(
+                count_tag(&x, tag),
+                expected.len(),
+                "count_tag({tag}) of {words:#x?}"
+            );
+            assert_eq!(
+                to_words(&filter_by_tag(&x, tag)),
+                expected,
+                "filter_by_tag({tag}) of {words:#x?}"
+            );
+        }
+    }
+
+    macro_rules! batch_tests {
+        ($($name:ident: $scheme:ty;)*) => {
+            proptest! {
+                $(
+                    #[test]
+                    fn $name(words in prop::collection::vec(word::<$scheme>(), 0..40)) {
+                        check::<$scheme>(&words);
+                    }
+                )*
+            }
+
+            /// Every length up to a few vector widths, so each of the vector
+            /// loops ends with every possible remainder.
+            #[test]
+            fn batch_matches_scalar_at_every_length() {
+                $(
+                    for len in 0..=33 {
+                        check::<$scheme>(&mixed_words::<$scheme>(len));
+                    }
+                )*
+            }
+        };
+    }
+
+    batch_tests! {
+        low_bits_batch_matches_scalar: LowBits<Basic>;
+        low_byte_batch_matches_scalar: LowByte<Basic>;
+        high_bits_batch_matches_scalar: HighBits<Basic>;
+        high_byte_batch_matches_scalar: HighByte<Basic>;
+        nan_boxing_batch_matches_scalar: NanBoxing<Basic>;
+    }
+
+    #[test]
+    fn finds_tags_outside_the_vector_path() {
+        // Tags above 7 in the low byte
+        let x: Vec<LowByte<Basic>> = from_words(&[0x1008, 0x20ff, 0x3008, 0x4001, 0x5008]);
+        assert_eq!(
+            tags_of::<_, 5>(x[..].try_into().unwrap()),
+            [8, 0xff, 8, 1, 8]
+        );
+        assert_eq!(count_tag(&x, 8), 3);
+        assert_eq!(to_words(&filter_by_tag(&x, 0xff)), [0x20ff]);
+        // Doubles and words with one bit of the NaN prefix missing
+        let valid = 0x7ffc_0000_0000_1003;
+        let words = [
+            1.5f64.to_bits(),
+            valid,
+            valid & !(1 << 60),
+            f64::NAN.to_bits(),
+            valid,
+        ];
+        let x: Vec<NanBoxing<Basic>> = from_words(&words);
+        assert_eq!(tags_of::<_, 5>(x[..].try_into().unwrap()), [9, 3, 9, 9, 3]);
+        assert_eq!(count_tag(&x, 3), 2);
+        assert_eq!(count_tag(&x, 9), 3);
+        assert_eq!(
+            to_words(&filter_by_tag(&x, 9)),
+            [words[0], words[2], words[3]]
+        );
+    }
+}
@@ -0,0 +1,112 @@
+// Bindings to the C implementations in c_version/schemes.c. Each scheme is
+// `repr(transparent)` (or `repr(u8)` for `Basic`), so slices can be handed to C
+// as is.
+use crate::types::*;
+
+macro_rules! c_kernels {
+    ($scheme:ty, $sum_t0:ident, $count_t1:ident, $sum_t0_t1:ident, $count_t1_t3:ident, $sum_t1_t3_t5:ident) => {
+        extern "C" {
+            fn $sum_t0(x: *const $scheme, len: usize) -> i32;
+            fn $count_t1(x: *const $scheme, len: usize) -> i32;
+            fn $sum_t0_t1(x: *const $scheme, len: usize) -> i32;
+            fn $count_t1_t3(x: *const $scheme, len: usize) -> i32;
+            fn $sum_t1_t3_t5(x: *const $scheme, len: usize) -> i32;
+        }
+
+        impl CKernels for $scheme {
+            fn sum_t0(x: &[Self]) -> i32 {
+                unsafe { $sum_t0(x.as_ptr(), x.len()) }
+            }
+            fn count_t1(x: &[Self]) -> i32 {
+                unsafe { $count_t1(x.as_ptr(), x.len()) }
+            }
+            fn sum_t0_t1(x: &[Self]) -> i32 {
+                unsafe { $sum_t0_t1(x.as_ptr(), x.len()) }
+            }
+            fn count_t1_t3(x: &[Self]) -> i32 {
+                unsafe { $count_t1_t3(x.as_ptr(), x.len()) }
+            }
+            fn sum_t1_t3_t5(x: &[Self]) -> i32 {
+                unsafe { $sum_t1_t3_t5(x.as_ptr(), x.len()) }
+            }
+        }
+    };
+}
+
+/// The C version of each kernel, for a scheme with the same layout in C.
+pub trait CKernels: Sized {
+    fn sum_t0(x: &[Self]) -> i32;
+    fn count_t1(x: &[Self]) -> i32;
+    fn sum_t0_t1(x: &[Self]) -> i32;
+    fn count_t1_t3(x: &[Self]) -> i32;
+    fn sum_t1_t3_t5(x: &[Self]) -> i32;
+}
+
+c_kernels!(
+    BaseLine,
+    c_sum_t0_baseline,
+    c_count_t1_baseline,
+    c_sum_t0_t1_baseline,
+    c_count_t1_t3_baseline,
+    c_sum_t1_t3_t5_baseline
+);
+c_kernels!(
+    LowBits<Basic>,
+    c_sum_t0_low_bits,
+    c_count_t1_low_bits,
+    c_sum_t0_t1_low_bits,
+    c_count_t1_t3_low_bits,
+    c_sum_t1_t3_t5_low_bits
+);
+c_kernels!(
+    LowByte<Basic>,
+    c_sum_t0_low_byte,
+    c_count_t1_low_byte,
+    c_sum_t0_t1_low_byte,
+    c_count_t1_t3_low_byte,
+    c_sum_t1_t3_t5_low_byte
+);
+c_kernels!(
+    HighBits<Basic>,
+    c_sum_t0_high_bits,
+    c_count_t1_high_bits,
+    c_sum_t0_t1_high_bits,
+    c_count_t1_t3_high_bits,
+    c_sum_t1_t3_t5_high_bits
+);
+c_kernels!(
+    HighByte<Basic>,
+    c_sum_t0_high_byte,
+    c_count_t1_high_byte,
+    c_sum_t0_t1_high_byte,
+    c_count_t1_t3_high_byte,
+    c_sum_t1_t3_t5_high_byte
+);
+c_kernels!(
+    NanBoxing<Basic>,
+    c_sum_t0_nan_boxing,
+    c_count_t1_nan_boxing,
+    c_sum_t0_t1_nan_boxing,
+    c_count_t1_t3_nan_boxing,
+    c_sum_t1_t3_t5_nan_boxing
+);
+
+pub fn c_sum_T0<T: CKernels>(x: &[T]) -> i32 {
+    T::sum_t0(x)
+}
+
+pub fn c_count_T1<T: CKernels>(x: &[T]) -> i32 {
+    T::count_t1(x)
+}
+
+pub fn c_sum_T0_T1<T: CKernels>(x: &[T]) -> i32 {
+    T::sum_t0_t1(x)
+}
+
+pub fn c_count_T1_T3<T: CKernels>(x: &[T]) -> i32 {
+    T::count_t1_t3(x)
+}
+
+pub fn c_sum_T1_T3_T5<T: CKernels>(x: &[T]) -> i32 {
+    T::sum_t1_t3_t5(x)
+}
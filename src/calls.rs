@@ -0,0 +1,67 @@
+// Calling convention pressure. Each `callee_<n>` takes n tagged values, decodes
+// every one of them and returns a new tagged value, and `call<n>` calls it
+// through a function pointer over a sliding window of the input. On x86-64
+// SysV the six integer argument registers fit six single word values but only
+// three `BaseLine`s, past which arguments are passed on the stack.
+use bumpalo::Bump;
+use criterion::black_box;
+
+use crate::gen_T1_T3_T5;
+use crate::harness::Elements;
+use crate::types::*;
+
+#[inline(always)]
+fn value<T: TaggedPointer<Basic>>(x: T) -> i32 {
+    unsafe { (*x.data().cast::<X<0>>()).data }.wrapping_add(x.tag() as i32)
+}
+
+macro_rules! arity {
+    ($n:literal, $ty:ident, $callee:ident, $call:ident, $gen:ident, $first:ident $(, $arg:ident)*) => {
+        pub struct $ty<T> {
+            values: Vec<T>,
+            callee: fn($first: T $(, $arg: T)*) -> T,
+        }
+
+        /// One call per window of the values.
+        impl<T> Elements for $ty<T> {
+            fn elements(&self) -> u64 {
+                (self.values.len() + 1).saturating_sub($n) as u64
+            }
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        pub fn $callee<T: TaggedPointer<Basic> + Copy>($first: T $(, $arg: T)*) -> T {
+            let sum = value($first) $(.wrapping_add(value($arg)))*;
+            T::from_raw($first.data(), (sum & 0b111) as u8)
+        }
+
+        pub fn $call<T: TaggedPointer<Basic> + Copy>(x: &$ty<T>) -> i32 {
+            let mut sum: i32 = 0;
+            for window in x.values.windows($n) {
+                let &[$first $(, $arg)*] = window else { unreachable!() };
+                sum = sum.wrapping_add(value((x.callee)($first $(, $arg)*)));
+            }
+            sum
+        }
+
+        pub fn $gen<T: TaggedPointer<Basic> + Copy>(bump: &Bump) -> $ty<T> {
+            $ty {
+                values: gen_T1_T3_T5(bump),
+                callee: black_box($callee as _),
+            }
+        }
+    };
+}
+
+arity!(1, Call1, callee_1, call1, gen_call1, a);
+arity!(2, Call2, callee_2, call2, gen_call2, a, b);
+arity!(3, Call3, callee_3, call3, gen_call3, a, b, c);
+arity!(4, Call4, callee_4, call4, gen_call4, a, b, c, d);
+arity!(5, Call5, callee_5, call5, gen_call5, a, b, c, d, e);
+arity!(6, Call6, callee_6, call6, gen_call6, a, b, c, d, e, f);
+arity!(7, Call7, callee_7, call7, gen_call7, a, b, c, d, e, f, g);
+arity!(8, Call8, callee_8, call8, gen_call8, a, b, c, d, e, f, g, h);
+arity!(9, Call9, callee_9, call9, gen_call9, a, b, c, d, e, f, g, h, i);
+arity!(10, Call10, callee_10, call10, gen_call10, a, b, c, d, e, f, g, h, i, j);
+arity!(11, Call11, callee_11, call11, gen_call11, a, b, c, d, e, f, g, h, i, j, k);
+arity!(12, Call12, callee_12, call12, gen_call12, a, b, c, d, e, f, g, h, i, j, k, l);
@@ -0,0 +1,175 @@
+// Pointer chasing workloads. Every hop loads a tagged value out of the
+// previous object, so the cost of untagging sits on the critical path of the
+// next load instead of being hidden behind independent iterations.
+//
+// Tag assignment:
+//   T0 => X<0> leaf
+//   T1 => Cons<T>
+//   T2 => Node<T>
+//   T3 => Entry<T>
+//   T7 => nil (null)
+use bumpalo::Bump;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::harness::Elements;
+use crate::rng;
+use crate::types::*;
+
+const LIST_LEN: usize = 10000;
+const TREE_SIZE: usize = 10000;
+const LOOKUPS: usize = 10000;
+// Prime, so the even keys of the table spread over every bucket
+const BUCKETS: usize = 1021;
+
+#[inline(always)]
+fn key_of<T: TaggedPointer<Basic>>(x: &T) -> i32 {
+    match x.untag() {
+        Basic::T0(x) => unsafe { (*x).data },
+        _ => 0,
+    }
+}
+
+pub struct Tree<T> {
+    root: T,
+    queries: Vec<i32>,
+}
+
+pub struct Chains<T> {
+    buckets: Vec<T>,
+    queries: Vec<i32>,
+}
+
+pub struct List<T> {
+    head: T,
+}
+
+impl<T> Elements for List<T> {
+    fn elements(&self) -> u64 {
+        LIST_LEN as u64
+    }
+}
+
+impl<T> Elements for Tree<T> {
+    fn elements(&self) -> u64 {
+        self.queries.len() as u64
+    }
+}
+
+impl<T> Elements for Chains<T> {
+    fn elements(&self) -> u64 {
+        self.queries.len() as u64
+    }
+}
+
+/// A single list whose cells are linked in a random order, so the walk
+/// can't be predicted by the hardware prefetcher.
+pub fn gen_list<T: TaggedPointer<Basic> + Copy>(bump: &Bump) -> List<T> {
+    let mut rng = rng();
+    let mut cells: Vec<*mut Cons<T>> = (0..LIST_LEN)
+        .map(|_| {
+            let car = leaf(bump, rng.gen_range(0..100));
+            bump.alloc(Cons { car, cdr: nil() }) as *mut _
+        })
+        .collect();
+    cells.shuffle(&mut rng);
+    let mut head = nil();
+    for cell in cells {
+        unsafe { (*cell).cdr = head };
+        head = T::new(Basic::T1(cell as *const X<0>));
+    }
+    List { head }
+}
+
+pub fn list_sum<T: TaggedPointer<Basic> + Copy>(list: &List<T>) -> i32 {
+    let mut sum: i32 = 0;
+    let mut cur = list.head;
+    while let Basic::T1(cell) = cur.untag() {
+        let cell = unsafe { &*cell.cast::<Cons<T>>() };
+        if let Basic::T0(x) = cell.car.untag() {
+            sum = sum.wrapping_add(unsafe { (*x).data });
+        }
+        cur = cell.cdr;
+    }
+    sum
+}
+
+fn build_tree<T: TaggedPointer<Basic> + Copy>(bump: &Bump, keys: &[i32]) -> T {
+    if keys.is_empty() {
+        return nil();
+    }
+    let mid = keys.len() / 2;
+    let left = build_tree(bump, &keys[..mid]);
+    let right = build_tree(bump, &keys[mid + 1..]);
+    let key = leaf(bump, keys[mid]);
+    let node = bump.alloc(Node { key, left, right });
+    T::new(Basic::T2(node as *const Node<T> as *const X<0>))
+}
+
+/// A balanced binary search tree and a set of keys to look up, half of
+/// which are present.
+pub fn gen_tree<T: TaggedPointer<Basic> + Copy>(bump: &Bump) -> Tree<T> {
+    let mut rng = rng();
+    // Only even keys are inserted so that odd queries miss
+    let keys: Vec<i32> = (0..TREE_SIZE as i32).map(|x| x * 2).collect();
+    let root = build_tree(bump, &keys);
+    let queries = (0..LOOKUPS)
+        .map(|_| rng.gen_range(0..TREE_SIZE as i32 * 2))
+        .collect();
+    Tree { root, queries }
+}
+
+pub fn tree_lookup<T: TaggedPointer<Basic> + Copy>(tree: &Tree<T>) -> i32 {
+    let mut found = 0;
+    for &query in &tree.queries {
+        let mut cur = tree.root;
+        while let Basic::T2(node) = cur.untag() {
+            let node = unsafe { &*node.cast::<Node<T>>() };
+            let key = key_of(&node.key);
+            if query == key {
+                found += 1;
+                break;
+            }
+            cur = if query < key { node.left } else { node.right };
+        }
+    }
+    found
+}
+
+/// A chained hash table with roughly ten entries per bucket, filled in a
+/// random order, and a set of keys to look up. Half of them are present, the
+/// other half miss after walking a whole chain.
+pub fn gen_chains<T: TaggedPointer<Basic> + Copy>(bump: &Bump) -> Chains<T> {
+    let mut rng = rng();
+    let mut buckets = vec![nil(); BUCKETS];
+    let mut keys: Vec<i32> = (0..TREE_SIZE as i32).map(|x| x * 2).collect();
+    keys.shuffle(&mut rng);
+    for &key in &keys {
+        let bucket = &mut buckets[key as usize % BUCKETS];
+        let entry = bump.alloc(Entry {
+            key: leaf(bump, key),
+            value: leaf(bump, key / 2),
+            next: *bucket,
+        });
+        *bucket = T::new(Basic::T3(entry as *const Entry<T> as *const X<0>));
+    }
+    let queries = (0..LOOKUPS)
+        .map(|_| rng.gen_range(0..TREE_SIZE as i32 * 2))
+        .collect();
+    Chains { buckets, queries }
+}
+
+pub fn hash_lookup<T: TaggedPointer<Basic> + Copy>(table: &Chains<T>) -> i32 {
+    let mut sum: i32 = 0;
+    for &query in &table.queries {
+        let mut cur = table.buckets[query as usize % BUCKETS];
+        while let Basic::T3(entry) = cur.untag() {
+            let entry = unsafe { &*entry.cast::<Entry<T>>() };
+            if key_of(&entry.key) == query {
+                sum = sum.wrapping_add(key_of(&entry.value));
+                break;
+            }
+            cur = entry.next;
+        }
+    }
+    sum
+}
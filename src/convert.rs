@@ -0,0 +1,129 @@
+// Re-encode values from one scheme in another, e.g. when a runtime switches
+// its encoding. Everything goes through `untag` and `new`, so any pair of
+// schemes over the same `Taggable` works.
+//
+//     let y: HighByte<Basic> = convert(x);
+//     let ys: Vec<HighByte<Basic>> = convert_vec(xs);
+use std::mem::{align_of, size_of, ManuallyDrop};
+
+use crate::types::*;
+
+#[inline(always)]
+pub fn convert<From, To, T>(x: From) -> To
+where
+    From: TaggedPointer<T>,
+    To: TaggedPointer<T>,
+    T: Taggable,
+{
+    To::new(x.untag())
+}
+
+pub fn convert_slice<From, To, T>(x: &[From]) -> Vec<To>
+where
+    From: TaggedPointer<T> + Copy,
+    To: TaggedPointer<T>,
+    T: Taggable,
+{
+    x.iter().map(|&x| convert(x)).collect()
+}
+
+/// Convert `x` without copying it. Panics unless both schemes have the same
+/// size and alignment, which holds for all the single word schemes.
+pub fn convert_in_place<From, To, T>(x: &mut [From]) -> &mut [To]
+where
+    From: TaggedPointer<T> + Copy,
+    To: TaggedPointer<T> + Copy,
+    T: Taggable,
+{
+    assert!(
+        size_of::<From>() == size_of::<To>() && align_of::<From>() == align_of::<To>(),
+        "in place conversion needs schemes with the same layout"
+    );
+    let len = x.len();
+    let from = x.as_mut_ptr();
+    let to = from.cast::<To>();
+    for i in 0..len {
+        // SAFETY: both types are Copy and have the same layout, and each
+        // element is read before it is overwritten
+        unsafe { to.add(i).write(convert(from.add(i).read())) };
+    }
+    unsafe { std::slice::from_raw_parts_mut(to, len) }
+}
+
+/// Convert `x`, reusing its allocation when the schemes have the same layout.
+pub fn convert_vec<From, To, T>(x: Vec<From>) -> Vec<To>
+where
+    From: TaggedPointer<T> + Copy,
+    To: TaggedPointer<T> + Copy,
+    T: Taggable,
+{
+    if size_of::<From>() != size_of::<To>() || align_of::<From>() != align_of::<To>() {
+        return convert_slice(&x);
+    }
+    let mut x = ManuallyDrop::new(x);
+    convert_in_place::<From, To, T>(&mut x);
+    // SAFETY: the buffer now holds `len` values of To, which has the same
+    // layout as From
+    unsafe { Vec::from_raw_parts(x.as_mut_ptr().cast::<To>(), x.len(), x.capacity()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values<S: TaggedPointer<Basic>>(objects: &[X<0>]) -> Vec<S> {
+        objects
+            .iter()
+            .enumerate()
+            .map(|(i, x)| S::from_raw(x as *const X<0> as *const u8, (i % 8) as u8))
+            .collect()
+    }
+
+    fn check<From, To>(objects: &[X<0>])
+    where
+        From: TaggedPointer<Basic> + Copy,
+        To: TaggedPointer<Basic> + Copy,
+    {
+        let from: Vec<From> = values(objects);
+        let expected: Vec<Basic> = from.iter().map(|x| x.untag()).collect();
+        let to: Vec<To> = convert_slice(&from);
+        assert_eq!(to.iter().map(|x| x.untag()).collect::<Vec<_>>(), expected);
+        let to: Vec<To> = convert_vec(from);
+        assert_eq!(to.iter().map(|x| x.untag()).collect::<Vec<_>>(), expected);
+    }
+
+    macro_rules! check_to {
+        ($objects:expr, $from:ty, $name:ident, $to:ty) => {
+            check::<$from, $to>($objects)
+        };
+    }
+
+    macro_rules! check_from {
+        ($objects:expr, $name:ident, $from:ty) => {
+            for_each_scheme!(check_to!($objects, $from,))
+        };
+    }
+
+    #[test]
+    fn every_pair_of_schemes_converts() {
+        let objects: Vec<X<0>> = (0..64).map(X::new).collect();
+        for_each_scheme!(check_from!(&objects,));
+    }
+
+    #[test]
+    fn convert_vec_reuses_the_allocation() {
+        let objects: Vec<X<0>> = (0..64).map(X::new).collect();
+        let from: Vec<LowBits<Basic>> = values(&objects);
+        let buffer = from.as_ptr() as usize;
+        let to: Vec<HighByte<Basic>> = convert_vec(from);
+        assert_eq!(to.as_ptr() as usize, buffer);
+    }
+
+    #[test]
+    #[should_panic(expected = "same layout")]
+    fn in_place_needs_the_same_layout() {
+        let objects = [X::new(1)];
+        let mut from: Vec<LowBits<Basic>> = values(&objects);
+        convert_in_place::<_, BaseLine, _>(&mut from);
+    }
+}
@@ -0,0 +1,178 @@
+// The same eight way dispatch written in ways that force a particular shape
+// of machine code, instead of leaving the choice to LLVM. Every variant does a
+// different operation so the arms can't be merged.
+use bumpalo::Bump;
+use rand::Rng;
+
+use crate::rng;
+use crate::types::*;
+
+const LEN: usize = 10000;
+
+const VARIANTS: [fn(*const X<0>) -> Basic; 8] = [
+    Basic::T0,
+    Basic::T1,
+    Basic::T2,
+    Basic::T3,
+    Basic::T4,
+    Basic::T5,
+    Basic::T6,
+    Basic::T7,
+];
+
+fn gen_mixed<T: TaggedPointer<Basic>>(bump: &Bump, tag: impl Fn(usize) -> usize) -> Vec<T> {
+    let mut rng = rng();
+    (0..LEN)
+        .map(|i| {
+            let data = bump.alloc(X::new(rng.gen_range(0..1000)));
+            T::new(VARIANTS[tag(i)](data))
+        })
+        .collect()
+}
+
+/// Uniformly random tags, so the dispatch is unpredictable.
+pub fn gen_random_tags<T: TaggedPointer<Basic>>(bump: &Bump) -> Vec<T> {
+    let mut rng = rng();
+    let tags: Vec<usize> = (0..LEN).map(|_| rng.gen_range(0..8)).collect();
+    gen_mixed(bump, |i| tags[i])
+}
+
+/// Tags cycling through every variant in order, which predictors learn.
+pub fn gen_cyclic_tags<T: TaggedPointer<Basic>>(bump: &Bump) -> Vec<T> {
+    gen_mixed(bump, |i| i % 8)
+}
+
+#[inline(always)]
+fn op0(sum: i32, data: i32) -> i32 {
+    sum.wrapping_add(data)
+}
+
+#[inline(always)]
+fn op1(sum: i32, data: i32) -> i32 {
+    sum.wrapping_sub(data)
+}
+
+#[inline(always)]
+fn op2(sum: i32, data: i32) -> i32 {
+    sum ^ data
+}
+
+#[inline(always)]
+fn op3(sum: i32, data: i32) -> i32 {
+    sum.wrapping_add(data.wrapping_mul(3))
+}
+
+#[inline(always)]
+fn op4(sum: i32, data: i32) -> i32 {
+    sum.wrapping_add(data >> 1)
+}
+
+#[inline(always)]
+fn op5(sum: i32, data: i32) -> i32 {
+    sum.rotate_left(data as u32 & 31)
+}
+
+#[inline(always)]
+fn op6(sum: i32, _data: i32) -> i32 {
+    sum.wrapping_add(1)
+}
+
+#[inline(always)]
+fn op7(sum: i32, data: i32) -> i32 {
+    sum | data
+}
+
+const OPS: [fn(i32, i32) -> i32; 8] = [op0, op1, op2, op3, op4, op5, op6, op7];
+
+#[inline(always)]
+fn data<T: TaggedPointer<Basic>>(x: &T) -> i32 {
+    unsafe { (*x.data().cast::<X<0>>()).data }
+}
+
+/// Whatever LLVM picks for a `match` on the untagged enum.
+pub fn dispatch_untag<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
+    let mut sum = 0;
+    for i in x {
+        sum = match i.untag() {
+            Basic::T0(x) => op0(sum, unsafe { (*x).data }),
+            Basic::T1(x) => op1(sum, unsafe { (*x).data }),
+            Basic::T2(x) => op2(sum, unsafe { (*x).data }),
+            Basic::T3(x) => op3(sum, unsafe { (*x).data }),
+            Basic::T4(x) => op4(sum, unsafe { (*x).data }),
+            Basic::T5(x) => op5(sum, unsafe { (*x).data }),
+            Basic::T6(x) => op6(sum, unsafe { (*x).data }),
+            Basic::T7(x) => op7(sum, unsafe { (*x).data }),
+        };
+    }
+    sum
+}
+
+/// An indirect call through a table of function pointers indexed by tag.
+pub fn dispatch_fn_table<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
+    let mut sum = 0;
+    for i in x {
+        sum = OPS[i.tag() as usize](sum, data(i));
+    }
+    sum
+}
+
+/// A dense `match` on the raw tag with a case for every value it can take,
+/// which LLVM lowers to an indirect jump through a jump table, the closest
+/// Rust gets to computed goto.
+pub fn dispatch_jump_table<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
+    let mut sum = 0;
+    for i in x {
+        let data = data(i);
+        sum = match i.tag() & 0b111 {
+            0 => op0(sum, data),
+            1 => op1(sum, data),
+            2 => op2(sum, data),
+            3 => op3(sum, data),
+            4 => op4(sum, data),
+            5 => op5(sum, data),
+            6 => op6(sum, data),
+            _ => op7(sum, data),
+        };
+    }
+    sum
+}
+
+/// A compare and branch per variant. The tags are hidden from the optimizer
+/// so it can't turn the chain back into a switch.
+pub fn dispatch_branch_chain<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
+    let tags: [u8; 8] = std::hint::black_box([0, 1, 2, 3, 4, 5, 6, 7]);
+    let mut sum = 0;
+    for i in x {
+        let tag = i.tag();
+        let data = data(i);
+        sum = if tag == tags[0] {
+            op0(sum, data)
+        } else if tag == tags[1] {
+            op1(sum, data)
+        } else if tag == tags[2] {
+            op2(sum, data)
+        } else if tag == tags[3] {
+            op3(sum, data)
+        } else if tag == tags[4] {
+            op4(sum, data)
+        } else if tag == tags[5] {
+            op5(sum, data)
+        } else if tag == tags[6] {
+            op6(sum, data)
+        } else {
+            op7(sum, data)
+        };
+    }
+    sum
+}
+
+/// No control flow at all: compute every arm and pick the result by tag.
+pub fn dispatch_select<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
+    let mut sum = 0;
+    for i in x {
+        let data = data(i);
+        let results = OPS.map(|op| op(sum, data));
+        sum = results[i.tag() as usize & 0b111];
+    }
+    sum
+}
@@ -0,0 +1,488 @@
+// One table of results for the whole run, read back from what criterion wrote
+// to `target/criterion`, and a comparison of two such tables. Only benchmarks
+// of the last run are exported, so a filtered run doesn't mix in stale results.
+//
+//     TAGGING_EXPORT=before.json cargo run --release
+//     ... change something ...
+//     TAGGING_EXPORT=after.json cargo run --release
+//     cargo run --release -- compare before.json after.json
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+/// Regressions smaller than this are ignored even when significant.
+pub const DEFAULT_THRESHOLD: f64 = 0.05;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Row {
+    pub workload: String,
+    pub scheme: String,
+    /// Elements per iteration
+    pub size: Option<u64>,
+    pub median_ns: f64,
+    /// 95% confidence interval of the median
+    pub median_lower_ns: f64,
+    pub median_upper_ns: f64,
+    /// `size` per second
+    pub throughput: Option<f64>,
+    /// `median_ns` over the `baseline` row of the same workload
+    pub relative: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct Benchmark {
+    group_id: String,
+    function_id: Option<String>,
+    throughput: Option<HashMap<String, u64>>,
+}
+
+#[derive(Deserialize)]
+struct Estimate {
+    confidence_interval: Interval,
+    point_estimate: f64,
+}
+
+#[derive(Deserialize)]
+struct Interval {
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+#[derive(Deserialize)]
+struct Estimates {
+    median: Estimate,
+}
+
+/// Where criterion writes its results, following the same rules it does.
+pub fn criterion_dir() -> PathBuf {
+    if let Some(home) = std::env::var_os("CRITERION_HOME") {
+        return home.into();
+    }
+    let target = std::env::var_os("CARGO_TARGET_DIR").unwrap_or_else(|| "target".into());
+    Path::new(&target).join("criterion")
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> io::Result<T> {
+    let file = fs::File::open(path)?;
+    serde_json::from_reader(io::BufReader::new(file)).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {e}", path.display()),
+        )
+    })
+}
+
+/// Every benchmark with results in `dir` and when they were written.
+fn read_results(dir: &Path) -> io::Result<Vec<(Row, SystemTime)>> {
+    let mut rows = Vec::new();
+    for group in fs::read_dir(dir)? {
+        let group = group?.path();
+        if !group.is_dir() {
+            continue;
+        }
+        for function in fs::read_dir(&group)? {
+            let new = function?.path().join("new");
+            if !new.join("estimates.json").exists() {
+                continue;
+            }
+            let benchmark: Benchmark = read_json(&new.join("benchmark.json"))?;
+            let estimates: Estimates = read_json(&new.join("estimates.json"))?;
+            let written = fs::metadata(new.join("estimates.json"))?.modified()?;
+            let size = benchmark.throughput.and_then(|x| x.into_values().next());
+            let median = estimates.median;
+            let row = Row {
+                workload: benchmark.group_id,
+                scheme: benchmark.function_id.unwrap_or_default(),
+                size,
+                median_ns: median.point_estimate,
+                median_lower_ns: median.confidence_interval.lower_bound,
+                median_upper_ns: median.confidence_interval.upper_bound,
+                throughput: size.map(|size| size as f64 / median.point_estimate * 1e9),
+                relative: None,
+            };
+            rows.push((row, written));
+        }
+    }
+    Ok(rows)
+}
+
+/// Fill in `relative` and sort by workload and then in scheme order.
+fn finish(mut rows: Vec<Row>) -> Vec<Row> {
+    let baselines: HashMap<String, f64> = rows
+        .iter()
+        .filter(|row| row.scheme == "baseline")
+        .map(|row| (row.workload.clone(), row.median_ns))
+        .collect();
+    for row in &mut rows {
+        row.relative = baselines
+            .get(&row.workload)
+            .map(|baseline| row.median_ns / baseline);
+    }
+    rows.sort_by(|a, b| {
+        (&a.workload, scheme_order(&a.scheme)).cmp(&(&b.workload, scheme_order(&b.scheme)))
+    });
+    rows
+}
+
+/// The benchmarks run by the last run of the harness, see `record_run`.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    /// `TAGGING_SEED` of the run
+    seed: u64,
+    /// `workload/scheme`
+    benchmarks: Vec<String>,
+}
+
+/// The results of one run and the seed its inputs were generated from.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    pub seed: u64,
+    pub rows: Vec<Row>,
+}
+
+const MANIFEST: &str = "tagging-run.json";
+
+fn id(row: &Row) -> String {
+    format!("{}/{}", row.workload, row.scheme)
+}
+
+/// Remember which benchmarks a run that started at `start` wrote results for,
+/// so a filtered run doesn't export results of earlier ones.
+pub fn record_run(dir: &Path, start: SystemTime, seed: u64) -> io::Result<()> {
+    let benchmarks = match read_results(dir) {
+        Ok(rows) => rows
+            .into_iter()
+            .filter(|(_, written)| *written >= start)
+            .map(|(row, _)| id(&row))
+            .collect(),
+        // Nothing was benchmarked, e.g. with `--bench '^$'`
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    fs::create_dir_all(dir)?;
+    fs::write(
+        dir.join(MANIFEST),
+        serde_json::to_string_pretty(&Manifest { seed, benchmarks })?,
+    )
+}
+
+/// The results of the last run recorded by `record_run`.
+pub fn load_run(dir: &Path) -> io::Result<Export> {
+    let manifest: Manifest = read_json(&dir.join(MANIFEST))?;
+    let rows = read_results(dir)?
+        .into_iter()
+        .map(|(row, _)| row)
+        .filter(|row| manifest.benchmarks.contains(&id(row)))
+        .collect();
+    Ok(Export {
+        seed: manifest.seed,
+        rows: finish(rows),
+    })
+}
+
+/// The order of `SCHEMES`, with rows like `tagged_vec` after them.
+pub fn scheme_order(scheme: &str) -> (usize, &str) {
+    let i = crate::probes::SCHEMES.iter().position(|&x| x == scheme);
+    (i.unwrap_or(usize::MAX), scheme)
+}
+
+fn opt<T: ToString>(x: Option<T>) -> String {
+    x.map_or(String::new(), |x| x.to_string())
+}
+
+/// One line per row, with the seed repeated in the last column.
+pub fn to_csv(export: &Export) -> String {
+    let mut out = String::from(
+        "workload,scheme,size,median_ns,median_lower_ns,median_upper_ns,throughput,relative,seed\n",
+    );
+    for row in &export.rows {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{}",
+            row.workload,
+            row.scheme,
+            opt(row.size),
+            row.median_ns,
+            row.median_lower_ns,
+            row.median_upper_ns,
+            opt(row.throughput),
+            opt(row.relative),
+            export.seed,
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Write `export` as CSV if `path` ends in `.csv`, otherwise as JSON.
+pub fn write(export: &Export, path: &Path) -> io::Result<()> {
+    let contents = if path.extension().is_some_and(|x| x == "csv") {
+        to_csv(export)
+    } else {
+        serde_json::to_string_pretty(export)?
+    };
+    fs::write(path, contents)
+}
+
+/// Export the results of the last run.
+pub fn export(path: &Path) -> io::Result<()> {
+    let export = load_run(&criterion_dir())?;
+    write(&export, path)?;
+    println!(
+        "exported {} results to {}",
+        export.rows.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Regressed,
+    Improved,
+    Unchanged,
+}
+
+/// Significant when the confidence intervals of the two medians don't overlap
+/// and the medians differ by more than `threshold`.
+pub fn change(old: &Row, new: &Row, threshold: f64) -> Change {
+    let ratio = new.median_ns / old.median_ns;
+    if new.median_lower_ns > old.median_upper_ns && ratio > 1.0 + threshold {
+        Change::Regressed
+    } else if new.median_upper_ns < old.median_lower_ns && ratio < 1.0 - threshold {
+        Change::Improved
+    } else {
+        Change::Unchanged
+    }
+}
+
+#[derive(Default)]
+struct Tally {
+    regressed: usize,
+    improved: usize,
+    compared: usize,
+    log_ratio: f64,
+}
+
+/// A Markdown report of every significant change between two runs, and a
+/// summary per scheme. Returns the report and whether anything regressed.
+pub fn compare(old: &[Row], new: &[Row], threshold: f64) -> (String, bool) {
+    let old: HashMap<(&str, &str), &Row> = old
+        .iter()
+        .map(|row| ((row.workload.as_str(), row.scheme.as_str()), row))
+        .collect();
+    let mut tallies: BTreeMap<(usize, &str), Tally> = BTreeMap::new();
+    let mut out = String::new();
+    writeln!(out, "# Changes over {:.0}%\n", threshold * 100.0).unwrap();
+    writeln!(out, "| workload | scheme | old ns | new ns | change | |").unwrap();
+    writeln!(out, "|---|---|---:|---:|---:|---|").unwrap();
+    for row in new {
+        let Some(before) = old.get(&(row.workload.as_str(), row.scheme.as_str())) else {
+            continue;
+        };
+        let tally = tallies.entry(scheme_order(&row.scheme)).or_default();
+        tally.compared += 1;
+        tally.log_ratio += (row.median_ns / before.median_ns).ln();
+        let flag = match change(before, row, threshold) {
+            Change::Regressed => {
+                tally.regressed += 1;
+                "regressed"
+            }
+            Change::Improved => {
+                tally.improved += 1;
+                "improved"
+            }
+            Change::Unchanged => continue,
+        };
+        writeln!(
+            out,
+            "| {} | {} | {:.1} | {:.1} | {:+.1}% | {flag} |",
+            row.workload,
+            row.scheme,
+            before.median_ns,
+            row.median_ns,
+            (row.median_ns / before.median_ns - 1.0) * 100.0
+        )
+        .unwrap();
+    }
+
+    writeln!(
+        out,
+        "\n| scheme | compared | regressed | improved | geomean change |"
+    )
+    .unwrap();
+    writeln!(out, "|---|---:|---:|---:|---:|").unwrap();
+    let mut regressed = false;
+    for ((_, scheme), tally) in &tallies {
+        regressed |= tally.regressed > 0;
+        let geomean = (tally.log_ratio / tally.compared as f64).exp();
+        writeln!(
+            out,
+            "| {scheme} | {} | {} | {} | {:+.1}% |",
+            tally.compared,
+            tally.regressed,
+            tally.improved,
+            (geomean - 1.0) * 100.0
+        )
+        .unwrap();
+    }
+    (out, regressed)
+}
+
+/// `compare <old.json> <new.json> [threshold %]`. Exits with 1 if anything
+/// regressed, so it can gate CI.
+pub fn compare_files(args: &[String]) -> io::Result<bool> {
+    let [old, new, rest @ ..] = args else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "usage: compare <old.json> <new.json> [threshold %]",
+        ));
+    };
+    let threshold = match rest {
+        [] => DEFAULT_THRESHOLD,
+        [percent] => {
+            percent.parse::<f64>().map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("threshold: {e}"))
+            })? / 100.0
+        }
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "too many arguments",
+            ))
+        }
+    };
+    let old: Export = read_json(Path::new(old))?;
+    let new: Export = read_json(Path::new(new))?;
+    if old.seed != new.seed {
+        eprintln!(
+            "warning: the runs used different seeds ({} and {}), so their inputs differ",
+            old.seed, new.seed
+        );
+    }
+    let (report, regressed) = compare(&old.rows, &new.rows, threshold);
+    print!("{report}");
+    Ok(regressed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every benchmark with results in `dir`, including ones left over from
+    /// earlier runs.
+    fn load(dir: &Path) -> io::Result<Vec<Row>> {
+        Ok(finish(
+            read_results(dir)?.into_iter().map(|(row, _)| row).collect(),
+        ))
+    }
+
+    fn row(workload: &str, scheme: &str, median_ns: f64) -> Row {
+        Row {
+            workload: workload.into(),
+            scheme: scheme.into(),
+            size: Some(10000),
+            median_ns,
+            median_lower_ns: median_ns * 0.99,
+            median_upper_ns: median_ns * 1.01,
+            throughput: None,
+            relative: None,
+        }
+    }
+
+    #[test]
+    fn load_reads_criterion_output() {
+        let dir = std::env::temp_dir().join(format!("tagging-export-{}", std::process::id()));
+        for (scheme, median, throughput) in [
+            ("baseline", 200.0, "{\"Elements\":10000}"),
+            ("high_byte", 100.0, "{\"Elements\":10000}"),
+        ] {
+            let new = dir.join("sum_T0").join(scheme).join("new");
+            fs::create_dir_all(&new).unwrap();
+            let benchmark = format!("{{\"group_id\":\"sum_T0\",\"function_id\":\"{scheme}\",\"throughput\":{throughput}}}");
+            fs::write(new.join("benchmark.json"), benchmark).unwrap();
+            let estimate = format!("{{\"confidence_interval\":{{\"lower_bound\":{},\"upper_bound\":{}}},\"point_estimate\":{median}}}", median - 1.0, median + 1.0);
+            fs::write(
+                new.join("estimates.json"),
+                format!("{{\"median\":{estimate}}}"),
+            )
+            .unwrap();
+        }
+        fs::create_dir_all(dir.join("report")).unwrap();
+        let rows = load(&dir).unwrap();
+        // Only high_byte was written after the run started
+        let baseline = dir.join("sum_T0/baseline/new/estimates.json");
+        let start = fs::metadata(&baseline).unwrap().modified().unwrap()
+            + std::time::Duration::from_nanos(1);
+        fs::File::options()
+            .write(true)
+            .open(dir.join("sum_T0/high_byte/new/estimates.json"))
+            .unwrap()
+            .set_modified(start)
+            .unwrap();
+        record_run(&dir, start, 42).unwrap();
+        let run = load_run(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(run.seed, 42);
+        assert_eq!(run.rows.len(), 1);
+        assert_eq!(
+            (run.rows[0].scheme.as_str(), run.rows[0].relative),
+            ("high_byte", None)
+        );
+        let json = serde_json::to_string(&run).unwrap();
+        assert_eq!(serde_json::from_str::<Export>(&json).unwrap(), run);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[0].scheme.as_str(), rows[1].scheme.as_str()),
+            ("baseline", "high_byte")
+        );
+        assert_eq!(rows[1].relative, Some(0.5));
+        assert_eq!(rows[1].throughput, Some(1e11));
+        assert_eq!(rows[1].median_lower_ns, 99.0);
+        let csv = to_csv(&Export { seed: 42, rows });
+        assert_eq!(
+            csv.lines().nth(2),
+            Some("sum_T0,high_byte,10000,100,99,101,100000000000,0.5,42")
+        );
+    }
+
+    #[test]
+    fn compare_flags_significant_changes() {
+        let old = [
+            row("sum_T0", "baseline", 100.0),
+            row("sum_T0", "low_bits", 100.0),
+            row("sum_T1", "low_bits", 100.0),
+        ];
+        let new = [
+            row("sum_T0", "baseline", 120.0),
+            row("sum_T0", "low_bits", 101.0),
+            row("sum_T1", "low_bits", 80.0),
+        ];
+        assert_eq!(
+            change(&old[0], &new[0], DEFAULT_THRESHOLD),
+            Change::Regressed
+        );
+        assert_eq!(
+            change(&old[1], &new[1], DEFAULT_THRESHOLD),
+            Change::Unchanged
+        );
+        assert_eq!(
+            change(&old[2], &new[2], DEFAULT_THRESHOLD),
+            Change::Improved
+        );
+        assert_eq!(change(&old[0], &new[0], 0.25), Change::Unchanged);
+
+        let (report, regressed) = compare(&old, &new, DEFAULT_THRESHOLD);
+        assert!(regressed);
+        assert!(report.contains("| sum_T0 | baseline | 100.0 | 120.0 | +20.0% | regressed |"));
+        assert!(report.contains("| low_bits | 2 | 0 | 1 | -10.1% |"));
+        assert!(!compare(&old, &old, DEFAULT_THRESHOLD).1);
+    }
+}
@@ -0,0 +1,68 @@
+// C API for the single word schemes. Every scheme is `repr(transparent)` over
+// a pointer, so it is passed and returned exactly like the struct wrapping a
+// pointer declared in c_version/tagging.h. Keep the two in sync.
+use crate::types::*;
+
+macro_rules! c_api {
+    ($scheme:ident, $reserved:expr, $make_tagged:ident, $tag_of:ident, $ptr_of:ident) => {
+        /// Aborts if `ptr` uses any of the bits reserved for the tag, or if
+        /// `tag` is not a `Basic` tag.
+        #[no_mangle]
+        pub extern "C" fn $make_tagged(ptr: *const u8, tag: u8) -> $scheme<Basic> {
+            let reserved: usize = $reserved;
+            assert!(
+                ptr as usize & reserved == 0,
+                concat!("pointer {:p} can't be encoded with ", stringify!($scheme)),
+                ptr
+            );
+            assert!(tag <= BasicTag::T7 as u8, "invalid tag {tag}");
+            $scheme::from_raw(ptr, tag)
+        }
+
+        #[no_mangle]
+        pub extern "C" fn $tag_of(x: $scheme<Basic>) -> u8 {
+            x.tag()
+        }
+
+        #[no_mangle]
+        pub extern "C" fn $ptr_of(x: $scheme<Basic>) -> *const u8 {
+            x.data()
+        }
+    };
+}
+
+c_api!(
+    LowBits,
+    0b111,
+    low_bits_make_tagged,
+    low_bits_tag_of,
+    low_bits_ptr_of
+);
+c_api!(
+    LowByte,
+    0xFF << 56,
+    low_byte_make_tagged,
+    low_byte_tag_of,
+    low_byte_ptr_of
+);
+c_api!(
+    HighBits,
+    0b111 << 61 | 0b111,
+    high_bits_make_tagged,
+    high_bits_tag_of,
+    high_bits_ptr_of
+);
+c_api!(
+    HighByte,
+    0xFF << 56,
+    high_byte_make_tagged,
+    high_byte_tag_of,
+    high_byte_ptr_of
+);
+c_api!(
+    NanBoxing,
+    1 << 63 | 0x7ffc000000000000 | 0b111,
+    nan_boxing_make_tagged,
+    nan_boxing_tag_of,
+    nan_boxing_ptr_of
+);
@@ -0,0 +1,107 @@
+// A mark-sweep collector over a heap of objects whose fields are tagged
+// values. Tracing is dominated by decoding fields and following pointers,
+// which is where the encoding matters most to a runtime.
+//
+// Tag assignment:
+//   T4 => Object<T>
+//   T6 => fixnum (immediate)
+//   T7 => nil
+use std::cell::Cell;
+
+use bumpalo::Bump;
+use rand::Rng;
+
+use crate::harness::Elements;
+use crate::rng;
+use crate::types::*;
+
+const HEAP_OBJECTS: usize = 10000;
+const FIELDS: usize = 4;
+const ROOTS: usize = 16;
+
+#[repr(C, align(8))]
+pub struct Object<T> {
+    marked: Cell<bool>,
+    fields: [T; FIELDS],
+}
+
+pub struct Heap<T> {
+    objects: Vec<*mut Object<T>>,
+    roots: Vec<T>,
+    /// Objects reachable from the roots, counted once by `gen_heap`
+    live: usize,
+}
+
+/// The objects the mark phase traces, not the whole heap.
+impl<T> Elements for Heap<T> {
+    fn elements(&self) -> u64 {
+        self.live as u64
+    }
+}
+
+#[inline(always)]
+fn object<T: TaggedPointer<Basic>>(obj: *const Object<T>) -> T {
+    T::new(Basic::T4(obj as *const X<0>))
+}
+
+/// A random object graph. Each field is a pointer to another object 60% of
+/// the time, otherwise a fixnum or nil.
+pub fn gen_heap<T: TaggedPointer<Basic> + Copy>(bump: &Bump) -> Heap<T> {
+    let mut rng = rng();
+    let nil = nil();
+    let objects: Vec<*mut Object<T>> = (0..HEAP_OBJECTS)
+        .map(|_| {
+            bump.alloc(Object {
+                marked: Cell::new(false),
+                fields: [nil; FIELDS],
+            }) as *mut _
+        })
+        .collect();
+    for &obj in &objects {
+        for field in 0..FIELDS {
+            let value = match rng.gen_range(0..10) {
+                0..=5 => object(objects[rng.gen_range(0..HEAP_OBJECTS)]),
+                6..=7 => fixnum(rng.gen()),
+                _ => nil,
+            };
+            unsafe { (*obj).fields[field] = value };
+        }
+    }
+    let roots = (0..ROOTS)
+        .map(|_| object(objects[rng.gen_range(0..HEAP_OBJECTS)]))
+        .collect();
+    let mut heap = Heap {
+        objects,
+        roots,
+        live: 0,
+    };
+    heap.live = mark_sweep(&heap) as usize;
+    heap
+}
+
+/// Mark everything reachable from the roots, then sweep the heap clearing
+/// the mark bits. Returns the number of live objects.
+pub fn mark_sweep<T: TaggedPointer<Basic> + Copy>(heap: &Heap<T>) -> i32 {
+    let mut worklist: Vec<T> = heap.roots.clone();
+    while let Some(value) = worklist.pop() {
+        if let Basic::T4(obj) = value.untag() {
+            let obj = unsafe { &*obj.cast::<Object<T>>() };
+            if !obj.marked.replace(true) {
+                for field in &obj.fields {
+                    if let Basic::T4(_) = field.untag() {
+                        worklist.push(*field);
+                    }
+                }
+            }
+        }
+    }
+
+    let mut live = 0;
+    for &obj in &heap.objects {
+        let obj = unsafe { &*obj };
+        if obj.marked.replace(false) {
+            live += 1;
+        }
+    }
+    live
+}
@@ -0,0 +1,72 @@
+// The benchmark macros are written against these traits instead of criterion
+// directly, so `all_benches` can also run every workload under hardware
+// counters, see `perf.rs`.
+use std::cell::RefCell;
+
+use criterion::measurement::WallTime;
+use criterion::{BenchmarkGroup, Criterion, Throughput};
+use tagging::tagged_vec::TaggedVec;
+use tagging::types::Taggable;
+
+pub trait Harness {
+    type Group<'a>: Group
+    where
+        Self: 'a;
+
+    fn benchmark_group(&mut self, name: &str) -> Self::Group<'_>;
+}
+
+pub trait Group {
+    fn throughput(&mut self, throughput: Throughput);
+    /// Measure `routine`, the row `id` of this group.
+    fn bench<O>(&mut self, id: &str, routine: impl FnMut() -> O);
+}
+
+impl Harness for Criterion {
+    type Group<'a> = BenchmarkGroup<'a, WallTime>;
+
+    fn benchmark_group(&mut self, name: &str) -> Self::Group<'_> {
+        Criterion::benchmark_group(self, name)
+    }
+}
+
+impl Group for BenchmarkGroup<'_, WallTime> {
+    fn throughput(&mut self, throughput: Throughput) {
+        BenchmarkGroup::throughput(self, throughput);
+    }
+
+    fn bench<O>(&mut self, id: &str, mut routine: impl FnMut() -> O) {
+        self.bench_function(id, |b| b.iter(&mut routine));
+    }
+}
+
+/// The number of elements a workload processes per iteration, which becomes
+/// the throughput of its group.
+pub trait Elements {
+    fn elements(&self) -> u64;
+}
+
+impl<T> Elements for Vec<T> {
+    fn elements(&self) -> u64 {
+        self.len() as u64
+    }
+}
+
+/// Values with some extra state, e.g. a function to call on them.
+impl<T, U> Elements for (Vec<T>, U) {
+    fn elements(&self) -> u64 {
+        self.0.len() as u64
+    }
+}
+
+impl<T> Elements for RefCell<Vec<T>> {
+    fn elements(&self) -> u64 {
+        self.borrow().len() as u64
+    }
+}
+
+impl<T: Taggable> Elements for TaggedVec<T> {
+    fn elements(&self) -> u64 {
+        self.len() as u64
+    }
+}
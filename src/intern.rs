@@ -0,0 +1,218 @@
+// Interning keyed on the encoded word, as in a symbol table or hash consing.
+// Each workload walks a stream of values and assigns every distinct one an id
+// in first seen order, so the result is the same for every scheme and only the
+// bits being hashed differ: tag in the low bits, the high byte or the NaN
+// space, and BaseLine's separate discriminant and pointer.
+//
+// `report` prints how well each scheme's words spread over an open addressing
+// table, which the timings alone don't show.
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher, Hash, Hasher};
+
+use bumpalo::Bump;
+use rand::Rng;
+use rustc_hash::FxBuildHasher;
+
+use crate::rng;
+use crate::types::*;
+
+const DISTINCT: usize = 4096;
+const STREAM: usize = 16384;
+const SLOTS: usize = 2 * DISTINCT;
+
+/// SipHash-1-3 like `RandomState` but with fixed keys, so runs are comparable.
+pub type SipBuildHasher = BuildHasherDefault<DefaultHasher>;
+
+/// No hashing at all: the word itself picks the slot. Only used by `report`, to
+/// show the layout the other hashers have to cope with.
+#[derive(Default)]
+pub struct IdentityHasher(u64);
+
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.write_u64(byte as u64);
+        }
+    }
+
+    // BaseLine hashes its discriminant and then its pointer
+    fn write_u64(&mut self, x: u64) {
+        self.0 = self.0.rotate_left(8) ^ x;
+    }
+
+    fn write_usize(&mut self, x: usize) {
+        self.write_u64(x as u64);
+    }
+
+    fn write_isize(&mut self, x: isize) {
+        self.write_u64(x as u64);
+    }
+}
+
+/// A stream of `STREAM` values drawn from `DISTINCT` objects with mixed tags.
+/// The objects are allocated one after the other, so their addresses differ
+/// only in a few middle bits.
+pub fn gen_interning<T: TaggedPointer<Basic> + Copy>(bump: &Bump) -> Vec<T> {
+    let mut rng = rng();
+    let distinct: Vec<T> = (0..DISTINCT)
+        .map(|i| {
+            T::from_raw(
+                bump.alloc(X::<0>::new(i as i32)) as *const X<0> as *const u8,
+                rng.gen_range(0..6),
+            )
+        })
+        .collect();
+    (0..STREAM)
+        .map(|_| distinct[rng.gen_range(0..DISTINCT)])
+        .collect()
+}
+
+/// Linear probing over a power of two number of slots, indexed by the low bits
+/// of the hash. It never grows, `intern` panics once it is full.
+pub struct OpenTable<K, S> {
+    slots: Vec<Option<(K, u32)>>,
+    len: usize,
+    hasher: S,
+    /// Slots looked at by all `intern` calls so far
+    pub probes: usize,
+}
+
+impl<K: Copy + Eq + Hash, S: BuildHasher + Default> OpenTable<K, S> {
+    pub fn with_slots(slots: usize) -> Self {
+        assert!(slots.is_power_of_two());
+        Self {
+            slots: vec![None; slots],
+            len: 0,
+            hasher: S::default(),
+            probes: 0,
+        }
+    }
+
+    /// The id of `key`, which is the number of keys interned before it.
+    pub fn intern(&mut self, key: K) -> u32 {
+        let mask = self.slots.len() - 1;
+        let mut i = self.hasher.hash_one(key) as usize & mask;
+        loop {
+            self.probes += 1;
+            match self.slots[i] {
+                Some((k, id)) if k == key => return id,
+                Some(_) => i = (i + 1) & mask,
+                None => {
+                    assert!(self.len < mask, "OpenTable is full");
+                    let id = self.len as u32;
+                    self.slots[i] = Some((key, id));
+                    self.len += 1;
+                    return id;
+                }
+            }
+        }
+    }
+
+    /// Distinct home slots among the keys, out of the number of keys. 1 means
+    /// no two keys hashed to the same slot.
+    pub fn spread(&self) -> f64 {
+        let mask = self.slots.len() - 1;
+        let mut homes: Vec<usize> = self
+            .slots
+            .iter()
+            .flatten()
+            .map(|&(k, _)| self.hasher.hash_one(k) as usize & mask)
+            .collect();
+        homes.sort_unstable();
+        homes.dedup();
+        homes.len() as f64 / self.len as f64
+    }
+}
+
+fn intern_hashmap<T: Copy + Eq + Hash, S: BuildHasher + Default>(x: &[T]) -> i32 {
+    let mut map: HashMap<T, u32, S> = HashMap::default();
+    let mut sum: i32 = 0;
+    for &i in x {
+        let next = map.len() as u32;
+        sum = sum.wrapping_add(*map.entry(i).or_insert(next) as i32);
+    }
+    sum
+}
+
+fn intern_open<T: Copy + Eq + Hash, S: BuildHasher + Default>(x: &[T]) -> i32 {
+    let mut table = OpenTable::<T, S>::with_slots(SLOTS);
+    let mut sum: i32 = 0;
+    for &i in x {
+        sum = sum.wrapping_add(table.intern(i) as i32);
+    }
+    sum
+}
+
+pub fn intern_hashmap_fx<T: Copy + Eq + Hash>(x: &[T]) -> i32 {
+    intern_hashmap::<T, FxBuildHasher>(x)
+}
+
+pub fn intern_hashmap_sip<T: Copy + Eq + Hash>(x: &[T]) -> i32 {
+    intern_hashmap::<T, SipBuildHasher>(x)
+}
+
+pub fn intern_open_fx<T: Copy + Eq + Hash>(x: &[T]) -> i32 {
+    intern_open::<T, FxBuildHasher>(x)
+}
+
+pub fn intern_open_sip<T: Copy + Eq + Hash>(x: &[T]) -> i32 {
+    intern_open::<T, SipBuildHasher>(x)
+}
+
+fn row<T: TaggedPointer<Basic> + Copy + Eq + Hash, S: BuildHasher + Default>(
+    out: &mut String,
+    bump: &Bump,
+) {
+    let mut table = OpenTable::<T, S>::with_slots(SLOTS);
+    for i in gen_interning::<T>(bump) {
+        table.intern(i);
+    }
+    write!(
+        out,
+        " {:.2} / {:.2} |",
+        table.probes as f64 / STREAM as f64,
+        table.spread()
+    )
+    .unwrap();
+}
+
+macro_rules! rows {
+    ($out:ident, $bump:ident, $($hasher:literal: $build:ty),*) => {
+        $(
+            write!($out, "| {} |", $hasher).unwrap();
+            row::<BaseLine, $build>(&mut $out, &$bump);
+            row::<LowBits<Basic>, $build>(&mut $out, &$bump);
+            row::<LowByte<Basic>, $build>(&mut $out, &$bump);
+            row::<HighBits<Basic>, $build>(&mut $out, &$bump);
+            row::<HighByte<Basic>, $build>(&mut $out, &$bump);
+            row::<NanBoxing<Basic>, $build>(&mut $out, &$bump);
+            writeln!($out).unwrap();
+        )*
+    };
+}
+
+/// Print a Markdown table of probes per lookup and home slot spread in
+/// `OpenTable` for every scheme and hasher.
+pub fn report() {
+    let bump = Bump::new();
+    let mut out = String::new();
+    writeln!(
+        out,
+        "# Interning {STREAM} values, {DISTINCT} distinct, in {SLOTS} slots\n"
+    )
+    .unwrap();
+    writeln!(out, "Probes per lookup / distinct home slots per key.\n").unwrap();
+    writeln!(
+        out,
+        "| hasher | baseline | low_bits | low_byte | high_bits | high_byte | nan_boxing |"
+    )
+    .unwrap();
+    writeln!(out, "|---|---:|---:|---:|---:|---:|---:|").unwrap();
+    rows!(out, bump, "identity": BuildHasherDefault<IdentityHasher>, "fx": FxBuildHasher, "sip": SipBuildHasher);
+    print!("{out}");
+}
@@ -0,0 +1,455 @@
+// A small stack based bytecode interpreter, generic over the tagging scheme.
+// This puts tag checks inside a realistic dispatch loop instead of a tight
+// kernel.
+//
+// Tag assignment:
+//   T1 => Cons<T>
+//   T6 => fixnum (immediate, stored in the pointer bits)
+//   T7 => nil
+use std::collections::HashMap;
+
+use bumpalo::Bump;
+
+use crate::harness::Elements;
+use crate::types::*;
+
+const LOCALS: usize = 8;
+
+#[derive(Copy, Clone, Debug)]
+pub enum Op {
+    /// Push a fixnum
+    Push(i32),
+    /// Push nil
+    Nil,
+    Load(u8),
+    Store(u8),
+    Dup,
+    Pop,
+    Add,
+    Sub,
+    Mul,
+    Rem,
+    /// ( a b -- a<b )
+    Lt,
+    /// ( car cdr -- cons )
+    Cons,
+    Car,
+    Cdr,
+    /// ( cons value -- )
+    SetCar,
+    /// ( cons value -- )
+    SetCdr,
+    IsCons,
+    IsNil,
+    IsFixnum,
+    Jump(u16),
+    /// Jump if the top of the stack is nil or fixnum 0
+    JumpIfFalse(u16),
+    Return,
+}
+
+pub struct Program {
+    code: Vec<Op>,
+}
+
+/// Every program loops `N` times.
+impl Elements for Program {
+    fn elements(&self) -> u64 {
+        N as u64
+    }
+}
+
+/// Builds a `Program`, resolving jump labels at the end.
+#[derive(Default)]
+struct Asm {
+    code: Vec<Op>,
+    labels: HashMap<&'static str, u16>,
+    fixups: Vec<(usize, &'static str)>,
+}
+
+impl Asm {
+    fn op(&mut self, op: Op) -> &mut Self {
+        self.code.push(op);
+        self
+    }
+
+    fn label(&mut self, name: &'static str) -> &mut Self {
+        self.labels.insert(name, self.code.len() as u16);
+        self
+    }
+
+    fn jump(&mut self, name: &'static str) -> &mut Self {
+        self.fixups.push((self.code.len(), name));
+        self.op(Op::Jump(0))
+    }
+
+    fn jump_if_false(&mut self, name: &'static str) -> &mut Self {
+        self.fixups.push((self.code.len(), name));
+        self.op(Op::JumpIfFalse(0))
+    }
+
+    fn finish(&mut self) -> Program {
+        let mut code = std::mem::take(&mut self.code);
+        for &(at, name) in &self.fixups {
+            let target = self.labels[name];
+            code[at] = match code[at] {
+                Op::Jump(_) => Op::Jump(target),
+                Op::JumpIfFalse(_) => Op::JumpIfFalse(target),
+                op => unreachable!("{op:?} is not a jump"),
+            };
+        }
+        Program { code }
+    }
+}
+
+#[inline(always)]
+fn boolean<T: TaggedPointer<Basic>>(x: bool) -> T {
+    fixnum(x as i32)
+}
+
+#[inline(always)]
+fn int<T: TaggedPointer<Basic>>(x: T) -> i32 {
+    match x.untag() {
+        Basic::T6(x) => (x as usize >> 3) as u32 as i32,
+        _ => panic!("expected a fixnum"),
+    }
+}
+
+#[inline(always)]
+fn cons<T: TaggedPointer<Basic>>(x: T) -> *mut Cons<T> {
+    match x.untag() {
+        Basic::T1(x) => x.cast::<Cons<T>>().cast_mut(),
+        _ => panic!("expected a cons"),
+    }
+}
+
+pub fn interpret<T: TaggedPointer<Basic> + Copy>(program: &Program) -> i32 {
+    let heap = Bump::new();
+    let code = &program.code;
+    let mut stack: Vec<T> = Vec::with_capacity(64);
+    let mut locals = [nil::<T>(); LOCALS];
+    let mut pc = 0;
+    macro_rules! pop {
+        () => {
+            stack.pop().expect("stack underflow")
+        };
+    }
+    loop {
+        let op = code[pc];
+        pc += 1;
+        match op {
+            Op::Push(n) => stack.push(fixnum(n)),
+            Op::Nil => stack.push(nil()),
+            Op::Load(i) => stack.push(locals[i as usize]),
+            Op::Store(i) => locals[i as usize] = pop!(),
+            Op::Dup => stack.push(*stack.last().expect("stack underflow")),
+            Op::Pop => {
+                pop!();
+            }
+            Op::Add => {
+                let (b, a) = (int(pop!()), int(pop!()));
+                stack.push(fixnum(a.wrapping_add(b)));
+            }
+            Op::Sub => {
+                let (b, a) = (int(pop!()), int(pop!()));
+                stack.push(fixnum(a.wrapping_sub(b)));
+            }
+            Op::Mul => {
+                let (b, a) = (int(pop!()), int(pop!()));
+                stack.push(fixnum(a.wrapping_mul(b)));
+            }
+            Op::Rem => {
+                let (b, a) = (int(pop!()), int(pop!()));
+                stack.push(fixnum(a.wrapping_rem(b)));
+            }
+            Op::Lt => {
+                let (b, a) = (int(pop!()), int(pop!()));
+                stack.push(boolean(a < b));
+            }
+            Op::Cons => {
+                let (cdr, car) = (pop!(), pop!());
+                let cell = heap.alloc(Cons { car, cdr });
+                stack.push(T::new(Basic::T1(cell as *mut Cons<T> as *const X<0>)));
+            }
+            Op::Car => {
+                let cell = cons(pop!());
+                stack.push(unsafe { (*cell).car });
+            }
+            Op::Cdr => {
+                let cell = cons(pop!());
+                stack.push(unsafe { (*cell).cdr });
+            }
+            Op::SetCar => {
+                let (value, cell) = (pop!(), cons(pop!()));
+                unsafe { (*cell).car = value };
+            }
+            Op::SetCdr => {
+                let (value, cell) = (pop!(), cons(pop!()));
+                unsafe { (*cell).cdr = value };
+            }
+            Op::IsCons => {
+                let x = pop!();
+                stack.push(boolean(matches!(x.untag(), Basic::T1(_))));
+            }
+            Op::IsNil => {
+                let x = pop!();
+                stack.push(boolean(matches!(x.untag(), Basic::T7(_))));
+            }
+            Op::IsFixnum => {
+                let x = pop!();
+                stack.push(boolean(matches!(x.untag(), Basic::T6(_))));
+            }
+            Op::Jump(target) => pc = target as usize,
+            Op::JumpIfFalse(target) => {
+                let falsy = match pop!().untag() {
+                    Basic::T7(_) => true,
+                    Basic::T6(x) => x.is_null(),
+                    _ => false,
+                };
+                if falsy {
+                    pc = target as usize;
+                }
+            }
+            Op::Return => return int(pop!()),
+        }
+    }
+}
+
+const N: i32 = 10000;
+
+/// Iterative fibonacci with wrapping arithmetic.
+pub fn fib_program(_: &Bump) -> Program {
+    const I: u8 = 0;
+    const A: u8 = 1;
+    const B: u8 = 2;
+    const TMP: u8 = 3;
+    Asm::default()
+        .op(Op::Push(0))
+        .op(Op::Store(A))
+        .op(Op::Push(1))
+        .op(Op::Store(B))
+        .op(Op::Push(N))
+        .op(Op::Store(I))
+        .label("loop")
+        .op(Op::Push(0))
+        .op(Op::Load(I))
+        .op(Op::Lt)
+        .jump_if_false("done")
+        .op(Op::Load(A))
+        .op(Op::Load(B))
+        .op(Op::Add)
+        .op(Op::Store(TMP))
+        .op(Op::Load(B))
+        .op(Op::Store(A))
+        .op(Op::Load(TMP))
+        .op(Op::Store(B))
+        .op(Op::Load(I))
+        .op(Op::Push(1))
+        .op(Op::Sub)
+        .op(Op::Store(I))
+        .jump("loop")
+        .label("done")
+        .op(Op::Load(A))
+        .op(Op::Return)
+        .finish()
+}
+
+/// Build the list (1 .. N) and then walk it summing the cars.
+pub fn list_sum_program(_: &Bump) -> Program {
+    const I: u8 = 0;
+    const LIST: u8 = 1;
+    const SUM: u8 = 2;
+    Asm::default()
+        .op(Op::Nil)
+        .op(Op::Store(LIST))
+        .op(Op::Push(N))
+        .op(Op::Store(I))
+        .label("build")
+        .op(Op::Push(0))
+        .op(Op::Load(I))
+        .op(Op::Lt)
+        .jump_if_false("sum")
+        .op(Op::Load(I))
+        .op(Op::Load(LIST))
+        .op(Op::Cons)
+        .op(Op::Store(LIST))
+        .op(Op::Load(I))
+        .op(Op::Push(1))
+        .op(Op::Sub)
+        .op(Op::Store(I))
+        .jump("build")
+        .label("sum")
+        .op(Op::Push(0))
+        .op(Op::Store(SUM))
+        .label("walk")
+        .op(Op::Load(LIST))
+        .op(Op::IsCons)
+        .jump_if_false("done")
+        .op(Op::Load(SUM))
+        .op(Op::Load(LIST))
+        .op(Op::Car)
+        .op(Op::Add)
+        .op(Op::Store(SUM))
+        .op(Op::Load(LIST))
+        .op(Op::Cdr)
+        .op(Op::Store(LIST))
+        .jump("walk")
+        .label("done")
+        .op(Op::Load(SUM))
+        .op(Op::Return)
+        .finish()
+}
+
+/// Insert N pseudo random keys into an unbalanced binary search tree. A node
+/// is `(key . (left . right))`.
+pub fn tree_insert_program(_: &Bump) -> Program {
+    const I: u8 = 0;
+    const ROOT: u8 = 1;
+    const KEY: u8 = 2;
+    const CUR: u8 = 3;
+    const CHILDREN: u8 = 4;
+    const DEPTH: u8 = 5;
+    let mut asm = Asm::default();
+    let new_node = |asm: &mut Asm| {
+        asm.op(Op::Load(KEY))
+            .op(Op::Nil)
+            .op(Op::Nil)
+            .op(Op::Cons)
+            .op(Op::Cons);
+    };
+    asm.op(Op::Nil)
+        .op(Op::Store(ROOT))
+        .op(Op::Push(0))
+        .op(Op::Store(DEPTH))
+        .op(Op::Push(N))
+        .op(Op::Store(I))
+        .label("next")
+        .op(Op::Push(0))
+        .op(Op::Load(I))
+        .op(Op::Lt)
+        .jump_if_false("done")
+        // key = i * 7919 % 10007
+        .op(Op::Load(I))
+        .op(Op::Push(7919))
+        .op(Op::Mul)
+        .op(Op::Push(10007))
+        .op(Op::Rem)
+        .op(Op::Store(KEY))
+        .op(Op::Load(I))
+        .op(Op::Push(1))
+        .op(Op::Sub)
+        .op(Op::Store(I))
+        .op(Op::Load(ROOT))
+        .op(Op::IsNil)
+        .jump_if_false("descend_root");
+    new_node(&mut asm);
+    asm.op(Op::Store(ROOT))
+        .jump("next")
+        .label("descend_root")
+        .op(Op::Load(ROOT))
+        .op(Op::Store(CUR))
+        .label("descend")
+        .op(Op::Load(DEPTH))
+        .op(Op::Push(1))
+        .op(Op::Add)
+        .op(Op::Store(DEPTH))
+        .op(Op::Load(CUR))
+        .op(Op::Cdr)
+        .op(Op::Store(CHILDREN))
+        .op(Op::Load(KEY))
+        .op(Op::Load(CUR))
+        .op(Op::Car)
+        .op(Op::Lt)
+        .jump_if_false("right")
+        .op(Op::Load(CHILDREN))
+        .op(Op::Car)
+        .op(Op::IsNil)
+        .jump_if_false("step_left")
+        .op(Op::Load(CHILDREN));
+    new_node(&mut asm);
+    asm.op(Op::SetCar)
+        .jump("next")
+        .label("step_left")
+        .op(Op::Load(CHILDREN))
+        .op(Op::Car)
+        .op(Op::Store(CUR))
+        .jump("descend")
+        .label("right")
+        .op(Op::Load(CHILDREN))
+        .op(Op::Cdr)
+        .op(Op::IsNil)
+        .jump_if_false("step_right")
+        .op(Op::Load(CHILDREN));
+    new_node(&mut asm);
+    asm.op(Op::SetCdr)
+        .jump("next")
+        .label("step_right")
+        .op(Op::Load(CHILDREN))
+        .op(Op::Cdr)
+        .op(Op::Store(CUR))
+        .jump("descend")
+        // The total number of nodes visited while inserting
+        .label("done")
+        .op(Op::Load(DEPTH))
+        .op(Op::Return)
+        .finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(program: fn(&Bump) -> Program, expected: i32) {
+        let program = program(&Bump::new());
+        assert_eq!(interpret::<BaseLine>(&program), expected);
+        assert_eq!(interpret::<LowBits<Basic>>(&program), expected);
+        assert_eq!(interpret::<LowByte<Basic>>(&program), expected);
+        assert_eq!(interpret::<HighBits<Basic>>(&program), expected);
+        assert_eq!(interpret::<HighByte<Basic>>(&program), expected);
+        assert_eq!(interpret::<NanBoxing<Basic>>(&program), expected);
+    }
+
+    #[test]
+    fn fib_matches_native() {
+        let (mut a, mut b) = (0i32, 1i32);
+        for _ in 0..N {
+            (a, b) = (b, a.wrapping_add(b));
+        }
+        check(fib_program, a);
+    }
+
+    #[test]
+    fn list_sum_is_triangular() {
+        check(list_sum_program, N * (N + 1) / 2);
+    }
+
+    #[test]
+    fn tree_insert_matches_native() {
+        // (key, left, right)
+        let mut nodes: Vec<(i32, Option<usize>, Option<usize>)> = Vec::new();
+        let mut depth = 0;
+        for i in (1..=N).rev() {
+            let key = i * 7919 % 10007;
+            if nodes.is_empty() {
+                nodes.push((key, None, None));
+                continue;
+            }
+            let mut cur = 0;
+            loop {
+                depth += 1;
+                let len = nodes.len();
+                let (cur_key, left, right) = &mut nodes[cur];
+                let child = if key < *cur_key { left } else { right };
+                match *child {
+                    Some(next) => cur = next,
+                    None => {
+                        *child = Some(len);
+                        nodes.push((key, None, None));
+                        break;
+                    }
+                }
+            }
+        }
+        check(tree_insert_program, depth);
+    }
+}
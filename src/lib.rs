@@ -0,0 +1,8 @@
+pub mod batch;
+pub mod convert;
+pub mod ffi;
+pub mod partition;
+pub mod snapshot;
+pub mod tagged_match;
+pub mod tagged_vec;
+pub mod types;
@@ -1,64 +1,230 @@
 #![allow(dead_code)]
 #![allow(non_snake_case)]
+use std::cell::RefCell;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
 use bumpalo::Bump;
 use concat_idents::concat_idents;
-
-mod types;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+mod asm;
+mod c_schemes;
+mod calls;
+mod chase;
+mod dispatch;
+mod export;
+mod gc;
+mod harness;
+mod intern;
+mod interp;
+mod perf;
+mod probes;
+mod summary;
+use c_schemes::*;
+use calls::*;
+use chase::*;
+use dispatch::*;
+use gc::{gen_heap, mark_sweep};
+use harness::{Elements, Group, Harness};
+use intern::*;
+use interp::{fib_program, interpret, list_sum_program, tree_insert_program};
+use tagging::batch::{count_tag, filter_by_tag, BatchTags};
+use tagging::convert::{convert_in_place, convert_slice};
+use tagging::partition::{partition_by_tag, partition_in_place, TagRanges};
+use tagging::tagged_match;
+use tagging::tagged_vec::TaggedVec;
+use tagging::types;
 use types::*;
 
 use criterion::*;
 
 const ILP: usize = 8;
 
+static SEED: OnceLock<u64> = OnceLock::new();
+
+/// The seed used by every data generator. Taken from the `TAGGING_SEED`
+/// environment variable, otherwise picked at random.
+fn seed() -> u64 {
+    *SEED.get_or_init(|| {
+        std::env::var("TAGGING_SEED")
+            .ok()
+            .map(|s| s.parse().expect("TAGGING_SEED must be a u64"))
+            .unwrap_or_else(rand::random)
+    })
+}
+
+/// A fresh generator for each data set, so every workload sees the same
+/// sequence regardless of which benchmarks were filtered out.
+fn rng() -> StdRng {
+    StdRng::seed_from_u64(seed())
+}
+
 macro_rules! bench_all {
     ($test:ident, $gen:ident, $c:ident) => {
         bench_all!($test, $test, $gen, $c);
     };
     ($name:ident, $test:ident, $gen:ident, $c:ident) => {{
-        let mut group = $c.benchmark_group(stringify!($name));
         let bump = Bump::new();
         let tagged = black_box($gen(&bump));
-        group.bench_function("baseline", |b| b.iter(|| $test::<types::BaseLine>(&tagged)));
+        let mut group = $c.benchmark_group(stringify!($name));
+        group.throughput(Throughput::Elements(tagged.elements()));
+        let expected = $test::<types::BaseLine>(&tagged);
+        group.bench("baseline", || $test::<types::BaseLine>(&tagged));
 
         let tagged = black_box($gen(&bump));
-        group.bench_function("low_bits", |b| b.iter(|| $test::<LowBits<_>>(&tagged)));
+        validate(
+            stringify!($name),
+            "low_bits",
+            $test::<LowBits<_>>(&tagged),
+            &expected,
+        );
+        group.bench("low_bits", || $test::<LowBits<_>>(&tagged));
 
         let tagged = black_box($gen(&bump));
-        group.bench_function("low_byte", |b| b.iter(|| $test::<LowByte<_>>(&tagged)));
+        validate(
+            stringify!($name),
+            "low_byte",
+            $test::<LowByte<_>>(&tagged),
+            &expected,
+        );
+        group.bench("low_byte", || $test::<LowByte<_>>(&tagged));
 
         let tagged = black_box($gen(&bump));
-        group.bench_function("high_bits", |b| b.iter(|| $test::<HighBits<_>>(&tagged)));
+        validate(
+            stringify!($name),
+            "high_bits",
+            $test::<HighBits<_>>(&tagged),
+            &expected,
+        );
+        group.bench("high_bits", || $test::<HighBits<_>>(&tagged));
 
         let tagged = black_box($gen(&bump));
-        group.bench_function("high_byte", |b| b.iter(|| $test::<HighByte<_>>(&tagged)));
+        validate(
+            stringify!($name),
+            "high_byte",
+            $test::<HighByte<_>>(&tagged),
+            &expected,
+        );
+        group.bench("high_byte", || $test::<HighByte<_>>(&tagged));
 
         let tagged = black_box($gen(&bump));
-        group.bench_function("nan_boxing", |b| b.iter(|| $test::<NanBoxing<_>>(&tagged)));
+        validate(
+            stringify!($name),
+            "nan_boxing",
+            $test::<NanBoxing<_>>(&tagged),
+            &expected,
+        );
+        group.bench("nan_boxing", || $test::<NanBoxing<_>>(&tagged));
     }};
 }
 
-macro_rules! gen {
-    ($variant:ident) => {
-        concat_idents!(fn_name = gen_, $variant {
-            fn fn_name<T: TaggedPointer<Basic> + Clone>(bump: &Bump) -> Vec<T> {
-                let basic = Basic::$variant(bump.alloc(X::new(37)));
-                vec![T::new(basic); 10000]
+// Add a `tagged_vec` row to the group `$name`, running `$test` on the same
+// values as `bench_all!` but stored in a `TaggedVec`.
+macro_rules! bench_tagged_vec {
+    ($name:ident, $test:ident, $gen:ident, $c:ident) => {{
+        let bump = Bump::new();
+        let tagged: TaggedVec<Basic> = $gen::<types::BaseLine>(&bump)
+            .iter()
+            .map(|x| x.untag())
+            .collect();
+        let tagged = black_box(tagged);
+        let mut group = $c.benchmark_group(stringify!($name));
+        group.throughput(Throughput::Elements(tagged.elements()));
+        let expected = $name::<types::BaseLine>(&$gen(&bump));
+        validate(stringify!($name), "tagged_vec", $test(&tagged), &expected);
+        group.bench("tagged_vec", || $test(&tagged));
+    }};
+}
+
+// The in place conversion only works between single word schemes, so unlike
+// `bench_all!` there is no baseline row; results are checked against
+// `checksum` of the BaseLine values instead.
+macro_rules! bench_in_place {
+    ($c:ident) => {{
+        let bump = Bump::new();
+        let values = gen_T0_T1_T2::<types::BaseLine>(&bump);
+        let expected = checksum(&values);
+        let mut group = $c.benchmark_group("convert_in_place_round_trip");
+        group.throughput(Throughput::Elements(values.elements()));
+        bench_in_place!(group, bump, expected, "low_bits", LowBits<_>);
+        bench_in_place!(group, bump, expected, "low_byte", LowByte<_>);
+        bench_in_place!(group, bump, expected, "high_bits", HighBits<_>);
+        bench_in_place!(group, bump, expected, "high_byte", HighByte<_>);
+        bench_in_place!(group, bump, expected, "nan_boxing", NanBoxing<_>);
+    }};
+    ($group:ident, $bump:ident, $expected:ident, $scheme:literal, $ty:ty) => {
+        let tagged = black_box(gen_T0_T1_T2_cell::<$ty>(&$bump));
+        validate(
+            "convert_in_place_round_trip",
+            $scheme,
+            convert_in_place_round_trip(&tagged),
+            &$expected,
+        );
+        $group.bench($scheme, || convert_in_place_round_trip(&tagged));
+    };
+}
+
+/// Panic unless a scheme computed the same result as `BaseLine`, so a broken
+/// encoding can't show up as a fast benchmark.
+fn validate<R: PartialEq + std::fmt::Debug>(group: &str, scheme: &str, actual: R, expected: &R) {
+    assert!(
+        actual == *expected,
+        "{group}/{scheme} returned {actual:?} but baseline returned {expected:?}"
+    );
+}
+
+// Generate `gen_<name>` which shares one object per variant, along with
+// `_in_order`, `_shuffled` and `_paged` versions that give every element its
+// own object.
+macro_rules! gen_placed {
+    ([$($name:tt),*], $reps:expr, $pattern:expr) => {
+        concat_idents!(fn_name = $($name),* {
+            fn fn_name<T: TaggedPointer<Basic>>(bump: &Bump) -> Vec<T> {
+                gen_pattern(bump, $pattern, $reps, Placement::Shared)
             }
         });
 
+        concat_idents!(fn_name = $($name),*, _in_order {
+            fn fn_name<T: TaggedPointer<Basic>>(bump: &Bump) -> Vec<T> {
+                gen_pattern(bump, $pattern, $reps, Placement::InOrder)
+            }
+        });
+
+        concat_idents!(fn_name = $($name),*, _shuffled {
+            fn fn_name<T: TaggedPointer<Basic>>(bump: &Bump) -> Vec<T> {
+                gen_pattern(bump, $pattern, $reps, Placement::Shuffled)
+            }
+        });
+
+        concat_idents!(fn_name = $($name),*, _paged {
+            fn fn_name<T: TaggedPointer<Basic>>(bump: &Bump) -> Vec<T> {
+                gen_pattern(bump, $pattern, $reps, Placement::Paged)
+            }
+        });
+    }
+}
+
+macro_rules! gen {
+    ($variant:ident) => {
+        gen_placed!([gen_, $variant], 10000, &[(Basic::$variant, 37)]);
+
         concat_idents!(fn_name = sum_, $variant {
             fn fn_name<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
                 sum(x, |i| match i.untag() {
                     Basic::$variant(x) => unsafe { (*x).data },
                     _ => 0,
                 })
-                // sum(x, |i| {
-                //     const TAG: u8 = (BasicTag::$variant as u8);
-                //     match i.tag() {
-                //         TAG => unsafe { (*i.data().cast::<X<0>>()).data },
-                //         _ => 0,
-                //     }
-                // })
+            }
+        });
+
+        concat_idents!(fn_name = match_sum_, $variant {
+            fn fn_name<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
+                sum(x, |i| tagged_match!(i, {
+                    Basic::$variant(x) => unsafe { (*x).data },
+                    _ => 0,
+                }))
             }
         });
 
@@ -76,6 +242,30 @@ macro_rules! gen {
                 count(x, |i| matches!(i.untag(), Basic::$variant(_)))
             }
         });
+
+        concat_idents!(fn_name = soa_sum_, $variant {
+            fn fn_name(x: &TaggedVec<Basic>) -> i32 {
+                sum_soa(x, |i| match i {
+                    Basic::$variant(x) => unsafe { (*x).data },
+                    _ => 0,
+                })
+            }
+        });
+
+        concat_idents!(fn_name = soa_sum_ilp_, $variant {
+            fn fn_name(x: &TaggedVec<Basic>) -> i32 {
+                sum_soa_ilp(x, |i| match i {
+                    Basic::$variant(x) => unsafe { (*x).data },
+                    _ => 0,
+                })
+            }
+        });
+
+        concat_idents!(fn_name = soa_count_, $variant {
+            fn fn_name(x: &TaggedVec<Basic>) -> i32 {
+                count_soa(x, |i| matches!(i, Basic::$variant(_)))
+            }
+        });
     }
 }
 
@@ -90,18 +280,7 @@ gen!(T7);
 
 macro_rules! gen2 {
     ($var1:ident, $var2:ident) => {
-        concat_idents!(fn_name = gen_, $var1, _, $var2 {
-            fn fn_name<T: TaggedPointer<Basic> + Clone>(bump: &Bump) -> Vec<T> {
-                let t0 = Basic::$var1(bump.alloc(X::new(37)));
-                let t1 = Basic::$var2(bump.alloc(X::new(33)));
-                let mut vec = Vec::new();
-                for _ in 0..5000 {
-                    vec.push(T::new(t0));
-                    vec.push(T::new(t1));
-                }
-                vec
-            }
-        });
+        gen_placed!([gen_, $var1, _, $var2], 5000, &[(Basic::$var1, 37), (Basic::$var2, 33)]);
 
         concat_idents!(fn_name = sum_, $var1, _, $var2 {
             fn fn_name<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
@@ -110,15 +289,16 @@ macro_rules! gen2 {
                     Basic::$var2(x) => unsafe { (*x).data },
                     _ => 0,
                 })
-                // sum(x, |i| {
-                //     const TAG1: u8 = (BasicTag::$var1 as u8);
-                //     const TAG2: u8 = (BasicTag::$var2 as u8);
-                //     match i.tag() {
-                //         TAG1 => unsafe { (*i.data().cast::<X<0>>()).data },
-                //         TAG2 => unsafe { (*i.data().cast::<X<0>>()).data },
-                //         _ => 0,
-                //     }
-                // })
+            }
+        });
+
+        concat_idents!(fn_name = match_sum_, $var1, _, $var2 {
+            fn fn_name<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
+                sum(x, |i| tagged_match!(i, {
+                    Basic::$var1(x) => unsafe { (*x).data },
+                    Basic::$var2(x) => unsafe { (*x).data },
+                    _ => 0,
+                }))
             }
         });
 
@@ -127,6 +307,22 @@ macro_rules! gen2 {
                 count(x, |i| matches!(i.untag(), Basic::$var1(_) | Basic::$var2(_)))
             }
         });
+
+        concat_idents!(fn_name = soa_sum_, $var1, _, $var2 {
+            fn fn_name(x: &TaggedVec<Basic>) -> i32 {
+                sum_soa(x, |i| match i {
+                    Basic::$var1(x) => unsafe { (*x).data },
+                    Basic::$var2(x) => unsafe { (*x).data },
+                    _ => 0,
+                })
+            }
+        });
+
+        concat_idents!(fn_name = soa_count_, $var1, _, $var2 {
+            fn fn_name(x: &TaggedVec<Basic>) -> i32 {
+                count_soa(x, |i| matches!(i, Basic::$var1(_) | Basic::$var2(_)))
+            }
+        });
     }
 }
 
@@ -137,20 +333,11 @@ gen2!(T1, T3);
 
 macro_rules! gen3 {
     ($var1:ident, $var2:ident, $var3:ident) => {
-        concat_idents!(fn_name = gen_, $var1, _, $var2, _, $var3 {
-            fn fn_name<T: TaggedPointer<Basic> + Clone>(bump: &Bump) -> Vec<T> {
-                let t0 = Basic::$var1(bump.alloc(X::new(37)));
-                let t1 = Basic::$var2(bump.alloc(X::new(33)));
-                let t2 = Basic::$var3(bump.alloc(X::new(17)));
-                let mut vec = Vec::new();
-                for _ in 0..3333 {
-                    vec.push(T::new(t0));
-                    vec.push(T::new(t1));
-                    vec.push(T::new(t2));
-                }
-                vec
-            }
-        });
+        gen_placed!(
+            [gen_, $var1, _, $var2, _, $var3],
+            3333,
+            &[(Basic::$var1, 37), (Basic::$var2, 33), (Basic::$var3, 17)]
+        );
 
         concat_idents!(fn_name = sum_, $var1, _, $var2, _, $var3 {
             fn fn_name<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
@@ -160,17 +347,17 @@ macro_rules! gen3 {
                     Basic::$var3(x) => unsafe { (*x).data },
                     _ => 0,
                 })
-                // sum(x, |i| {
-                //     const TAG1: u8 = (BasicTag::$var1 as u8);
-                //     const TAG2: u8 = (BasicTag::$var2 as u8);
-                //     const TAG3: u8 = (BasicTag::$var3 as u8);
-                //     match i.tag() {
-                //         TAG1  => unsafe { (*i.data().cast::<X<0>>()).data },
-                //         TAG2 => unsafe { (*i.data().cast::<X<0>>()).data },
-                //         TAG3 => unsafe { (*i.data().cast::<X<0>>()).data },
-                //         _ => 0,
-                //     }
-                // })
+            }
+        });
+
+        concat_idents!(fn_name = match_sum_, $var1, _, $var2, _, $var3 {
+            fn fn_name<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
+                sum(x, |i| tagged_match!(i, {
+                    Basic::$var1(x) => unsafe { (*x).data },
+                    Basic::$var2(x) => unsafe { (*x).data },
+                    Basic::$var3(x) => unsafe { (*x).data },
+                    _ => 0,
+                }))
             }
         });
 
@@ -179,6 +366,23 @@ macro_rules! gen3 {
                 count(x, |i| matches!(i.untag(), Basic::$var1(_) | Basic::$var2(_) | Basic::$var3(_)))
             }
         });
+
+        concat_idents!(fn_name = soa_sum_, $var1, _, $var2, _, $var3 {
+            fn fn_name(x: &TaggedVec<Basic>) -> i32 {
+                sum_soa(x, |i| match i {
+                    Basic::$var1(x) => unsafe { (*x).data },
+                    Basic::$var2(x) => unsafe { (*x).data },
+                    Basic::$var3(x) => unsafe { (*x).data },
+                    _ => 0,
+                })
+            }
+        });
+
+        concat_idents!(fn_name = soa_count_, $var1, _, $var2, _, $var3 {
+            fn fn_name(x: &TaggedVec<Basic>) -> i32 {
+                count_soa(x, |i| matches!(i, Basic::$var1(_) | Basic::$var2(_) | Basic::$var3(_)))
+            }
+        });
     }
 }
 
@@ -195,6 +399,66 @@ fn sum<T: TaggedPointer<Basic>>(x: &[T], f: impl Fn(&T) -> i32) -> i32 {
     sum
 }
 
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Placement {
+    /// Every element with the same variant points at the same object
+    Shared,
+    /// A fresh object per element, laid out in element order
+    InOrder,
+    /// A fresh object per element, assigned to elements in random order
+    Shuffled,
+    /// A fresh object per element, each on its own page
+    Paged,
+}
+
+const PAGE_SIZE: usize = 4096;
+
+type Variant = fn(*const X<0>) -> Basic;
+
+/// Repeat `pattern` `reps` times, allocating the pointees according to
+/// `placement`.
+fn gen_pattern<T: TaggedPointer<Basic>>(
+    bump: &Bump,
+    pattern: &[(Variant, i32)],
+    reps: usize,
+    placement: Placement,
+) -> Vec<T> {
+    let alloc = |data: i32| -> *mut X<0> {
+        if placement == Placement::Paged {
+            let layout = std::alloc::Layout::from_size_align(PAGE_SIZE, PAGE_SIZE).unwrap();
+            let ptr = bump.alloc_layout(layout).cast::<X<0>>().as_ptr();
+            unsafe { ptr.write(X::new(data)) };
+            ptr
+        } else {
+            bump.alloc(X::new(data))
+        }
+    };
+
+    if placement == Placement::Shared {
+        let shared: Vec<Basic> = pattern
+            .iter()
+            .map(|(variant, data)| variant(alloc(*data)))
+            .collect();
+        return (0..reps)
+            .flat_map(|_| shared.iter().map(|&x| T::new(x)))
+            .collect();
+    }
+
+    let mut objects: Vec<*mut X<0>> = (0..reps * pattern.len()).map(|_| alloc(0)).collect();
+    if placement == Placement::Shuffled {
+        objects.shuffle(&mut rng());
+    }
+    objects
+        .chunks(pattern.len())
+        .flat_map(|chunk| {
+            chunk.iter().zip(pattern).map(|(&ptr, (variant, data))| {
+                unsafe { (*ptr).data = *data };
+                T::new(variant(ptr))
+            })
+        })
+        .collect()
+}
+
 fn gen_values(bump: &Bump) -> Vec<*const u8> {
     let value = bump.alloc(X::new(37));
     vec![value as *const X<0> as *const u8; 10000]
@@ -204,9 +468,8 @@ fn elide<T: TaggedPointer<Basic>>(source: &[*const u8]) -> i32 {
     let mut sum = 0;
     for ptr in source {
         let tagged = T::from_raw(*ptr, 1);
-        match tagged.untag() {
-            Basic::T1(x) => sum += unsafe {(*x).data },
-            _ => {},
+        if let Basic::T1(x) = tagged.untag() {
+            sum += unsafe { (*x).data };
         }
     }
     sum
@@ -234,6 +497,35 @@ fn count<T: TaggedPointer<Basic>>(x: &[T], f: impl Fn(&T) -> bool) -> i32 {
     sum
 }
 
+fn sum_soa(x: &TaggedVec<Basic>, f: impl Fn(Basic) -> i32) -> i32 {
+    let mut sum = 0;
+    for i in x {
+        sum += f(i);
+    }
+    sum
+}
+
+fn sum_soa_ilp(x: &TaggedVec<Basic>, f: impl Fn(Basic) -> i32) -> i32 {
+    let mut sum1 = 0;
+    let mut sum2 = 0;
+    let mut iter = x.iter();
+    while let (Some(a), Some(b)) = (iter.next(), iter.next()) {
+        sum1 += f(a);
+        sum2 += f(b);
+    }
+    sum2 + sum1
+}
+
+fn count_soa(x: &TaggedVec<Basic>, f: impl Fn(Basic) -> bool) -> i32 {
+    let mut sum = 0;
+    for i in x {
+        if f(i) {
+            sum += 1;
+        }
+    }
+    sum
+}
+
 fn sum_chunk_t0<T: TaggedPointer<Basic>>(x: &[[T; ILP]]) -> i32 {
     let mut sum: i32 = 0;
     for i in x {
@@ -265,26 +557,6 @@ fn sum_chunk_t0<T: TaggedPointer<Basic>>(x: &[[T; ILP]]) -> i32 {
     sum
 }
 
-fn call7<T: TaggedPointer<Basic> + Copy>(x: &(Vec<T>, fn(T, T, T, T, T, T, T) -> i32)) -> i32 {
-    let mut sum: i32 = 0;
-    let f = x.1;
-    let x = &x.0;
-    for i in x {
-        sum += f(*i, *i, *i, *i, *i, *i, *i);
-    }
-    sum
-}
-
-fn call8<T: TaggedPointer<Basic> + Copy>(x: &(Vec<T>, fn(T, T, T, T, T, T, T, T) -> i32)) -> i32 {
-    let mut sum: i32 = 0;
-    let f = x.1;
-    let x = &x.0;
-    for i in x {
-        sum += f(*i, *i, *i, *i, *i, *i, *i, *i);
-    }
-    sum
-}
-
 fn count_t0_to_t3<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
     let mut sum: i32 = 0;
     for i in x {
@@ -317,10 +589,11 @@ fn sum_all<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
 
 fn gen_basic_data(bump: &Bump) -> Vec<Basic> {
     // generate a vector of random Basic Values
+    let mut rng = rng();
     let mut x = Vec::new();
     for _ in 0..10000 {
-        // generate a random number between 0 and 7 using rand crate
-        let rand = rand::random::<u32>();
+        // generate a random number between 0 and 7
+        let rand = rng.gen::<u32>();
         let variant = rand % 8;
         let basic = match variant {
             0 => Basic::T0(bump.alloc(X::new(rand as i32))),
@@ -340,10 +613,10 @@ fn gen_basic_data(bump: &Bump) -> Vec<Basic> {
 
 fn gen_predictable_data(bump: &Bump) -> Vec<Basic> {
     // generate a vector of random Basic Values
+    let mut rng = rng();
     let mut x = Vec::new();
     for _ in 0..10000 {
-        // generate a random number between 0 and 7 using rand crate
-        let rand = rand::random::<u32>();
+        let rand = rng.gen::<u32>();
         let basic = Basic::T0(bump.alloc(X::new(rand as i32)));
         x.push(basic);
     }
@@ -360,27 +633,111 @@ fn gen_t0_set<T: TaggedPointer<Basic> + Copy>(bump: &Bump) -> Vec<[T; 8]> {
     vec![array; 10000]
 }
 
-fn gen_t1_call7<T: TaggedPointer<Basic> + Clone + Copy>(
-    bump: &Bump,
-) -> (Vec<T>, fn(T, T, T, T, T, T, T) -> i32) {
-    let basic = Basic::T1(bump.alloc(X::new(37)));
-    (
-        vec![T::new(basic); 10000],
-        black_box(|_, _, _, _, _, _, _| 13),
-    )
+fn batch_count_T0<T: BatchTags>(x: &[T]) -> i32 {
+    count_tag(x, BasicTag::T0 as u8) as i32
 }
 
-fn gen_t1_call8<T: TaggedPointer<Basic> + Clone + Copy>(
-    bump: &Bump,
-) -> (Vec<T>, fn(T, T, T, T, T, T, T, T) -> i32) {
-    let basic = Basic::T1(bump.alloc(X::new(37)));
-    (
-        vec![T::new(basic); 10000],
-        black_box(|_, _, _, _, _, _, _, _| 13),
-    )
+fn batch_count_T1_T3<T: BatchTags>(x: &[T]) -> i32 {
+    (count_tag(x, BasicTag::T1 as u8) + count_tag(x, BasicTag::T3 as u8)) as i32
 }
 
-fn all_benches(c: &mut Criterion) {
+fn batch_filter_T1<T: BatchTags>(x: &[T]) -> i32 {
+    filter_by_tag(x, BasicTag::T1 as u8).len() as i32
+}
+
+fn batch_tags_of<T: BatchTags>(x: &[T]) -> i32 {
+    let mut tags = [0; 256];
+    let mut sum = 0;
+    for chunk in x.chunks(tags.len()) {
+        T::tags_into(chunk, &mut tags);
+        sum += tags[..chunk.len()].iter().map(|&t| t as i32).sum::<i32>();
+    }
+    sum
+}
+
+/// Sum the pointees of a slice where every value is known to have the same
+/// tag, so no tag checks are needed.
+fn sum_homogeneous<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
+    let mut sum = 0;
+    for i in x {
+        sum += unsafe { (*i.data().cast::<X<0>>()).data };
+    }
+    sum
+}
+
+fn partition_count_T0_T2<T: TaggedPointer<Basic> + Copy>(x: &[T]) -> i32 {
+    let buckets = partition_by_tag(x);
+    (buckets.get(BasicTag::T0 as u8).len() + buckets.get(BasicTag::T2 as u8).len()) as i32
+}
+
+fn partition_sum_T1_T3_T5<T: TaggedPointer<Basic> + Copy>(x: &[T]) -> i32 {
+    let buckets = partition_by_tag(x);
+    sum_homogeneous(buckets.get(BasicTag::T1 as u8))
+        + sum_homogeneous(buckets.get(BasicTag::T3 as u8))
+        + sum_homogeneous(buckets.get(BasicTag::T5 as u8))
+}
+
+fn gen_T0_T2_partitioned<T: TaggedPointer<Basic>>(bump: &Bump) -> (Vec<T>, TagRanges) {
+    let mut x = gen_T0_T2(bump);
+    let ranges = partition_in_place(&mut x);
+    (x, ranges)
+}
+
+fn gen_T1_T3_T5_partitioned<T: TaggedPointer<Basic>>(bump: &Bump) -> (Vec<T>, TagRanges) {
+    let mut x = gen_T1_T3_T5(bump);
+    let ranges = partition_in_place(&mut x);
+    (x, ranges)
+}
+
+fn partitioned_count_T0_T2<T>((_, ranges): &(Vec<T>, TagRanges)) -> i32 {
+    (ranges.get(BasicTag::T0 as u8).len() + ranges.get(BasicTag::T2 as u8).len()) as i32
+}
+
+fn partitioned_sum_T1_T3_T5<T: TaggedPointer<Basic>>((x, ranges): &(Vec<T>, TagRanges)) -> i32 {
+    sum_homogeneous(&x[ranges.get(BasicTag::T1 as u8)])
+        + sum_homogeneous(&x[ranges.get(BasicTag::T3 as u8)])
+        + sum_homogeneous(&x[ranges.get(BasicTag::T5 as u8)])
+}
+
+/// Tags plus pointees, enough to catch a conversion that loses either.
+fn checksum<T: TaggedPointer<Basic>>(x: &[T]) -> i32 {
+    let mut sum: i32 = 0;
+    for i in x {
+        let data = unsafe { (*i.data().cast::<X<0>>()).data };
+        sum = sum.wrapping_add(data).wrapping_add(i.tag() as i32);
+    }
+    sum
+}
+
+// Copy a slice of each scheme into a new Vec of the target scheme.
+macro_rules! convert_to {
+    ($name:ident, $to:ty) => {
+        fn $name<T: TaggedPointer<Basic> + Copy>(x: &[T]) -> i32 {
+            checksum(&convert_slice::<T, $to, Basic>(x))
+        }
+    };
+}
+
+convert_to!(convert_to_baseline, BaseLine);
+convert_to!(convert_to_low_bits, LowBits<Basic>);
+convert_to!(convert_to_high_byte, HighByte<Basic>);
+convert_to!(convert_to_nan_boxing, NanBoxing<Basic>);
+
+fn gen_T0_T1_T2_cell<T: TaggedPointer<Basic>>(bump: &Bump) -> RefCell<Vec<T>> {
+    RefCell::new(gen_T0_T1_T2(bump))
+}
+
+/// Convert to `HighByte` and back without allocating, leaving the input as it
+/// was for the next iteration.
+fn convert_in_place_round_trip<T: TaggedPointer<Basic> + Copy>(x: &RefCell<Vec<T>>) -> i32 {
+    let mut x = x.borrow_mut();
+    let converted = convert_in_place::<T, HighByte<Basic>, Basic>(&mut x);
+    let sum = checksum(converted);
+    convert_in_place::<HighByte<Basic>, T, Basic>(converted);
+    sum
+}
+
+fn all_benches<H: Harness>(c: &mut H) {
     bench_all!(sum_T0, gen_T0, c);
     bench_all!(sum_T1, gen_T1, c);
     bench_all!(sum_ilp_T1, gen_T1, c);
@@ -389,11 +746,11 @@ fn all_benches(c: &mut Criterion) {
     bench_all!(count_T1, gen_T1, c);
     bench_all!(count_T7, gen_T7, c);
     bench_all!(sum_T0_T1, gen_T0_T1, c);
-    bench_all!(sum_T0_T2, gen_T0_T1, c);
+    bench_all!(sum_T0_T2, gen_T0_T2, c);
     bench_all!(sum_T1_T2, gen_T1_T2, c);
     bench_all!(sum_T1_T3, gen_T1_T3, c);
     bench_all!(count_T0_T1, gen_T0_T1, c);
-    bench_all!(count_T0_T2, gen_T0_T1, c);
+    bench_all!(count_T0_T2, gen_T0_T2, c);
     bench_all!(count_T1_T2, gen_T1_T2, c);
     bench_all!(count_T1_T3, gen_T1_T3, c);
     bench_all!(sum_T0_T1_T2, gen_T0_T1_T2, c);
@@ -405,139 +762,170 @@ fn all_benches(c: &mut Criterion) {
     bench_all!(count_T1_T2_T3, gen_T1_T2_T3, c);
     bench_all!(count_T1_T3_T5, gen_T1_T3_T5, c);
 
+    bench_all!(match_sum_T0, gen_T0, c);
+    bench_all!(match_sum_T1, gen_T1, c);
+    bench_all!(match_sum_T0_T1, gen_T0_T1, c);
+    bench_all!(match_sum_T1_T3, gen_T1_T3, c);
+    bench_all!(match_sum_T0_T1_T2, gen_T0_T1_T2, c);
+    bench_all!(match_sum_T1_T3_T5, gen_T1_T3_T5, c);
+
+    bench_tagged_vec!(sum_T0, soa_sum_T0, gen_T0, c);
+    bench_tagged_vec!(sum_T1, soa_sum_T1, gen_T1, c);
+    bench_tagged_vec!(sum_ilp_T1, soa_sum_ilp_T1, gen_T1, c);
+    bench_tagged_vec!(sum_T7, soa_sum_T7, gen_T1, c);
+    bench_tagged_vec!(count_T0, soa_count_T0, gen_T0, c);
+    bench_tagged_vec!(count_T1, soa_count_T1, gen_T1, c);
+    bench_tagged_vec!(count_T7, soa_count_T7, gen_T7, c);
+    bench_tagged_vec!(sum_T0_T1, soa_sum_T0_T1, gen_T0_T1, c);
+    bench_tagged_vec!(sum_T0_T2, soa_sum_T0_T2, gen_T0_T2, c);
+    bench_tagged_vec!(sum_T1_T2, soa_sum_T1_T2, gen_T1_T2, c);
+    bench_tagged_vec!(sum_T1_T3, soa_sum_T1_T3, gen_T1_T3, c);
+    bench_tagged_vec!(count_T0_T1, soa_count_T0_T1, gen_T0_T1, c);
+    bench_tagged_vec!(count_T0_T2, soa_count_T0_T2, gen_T0_T2, c);
+    bench_tagged_vec!(count_T1_T2, soa_count_T1_T2, gen_T1_T2, c);
+    bench_tagged_vec!(count_T1_T3, soa_count_T1_T3, gen_T1_T3, c);
+    bench_tagged_vec!(sum_T0_T1_T2, soa_sum_T0_T1_T2, gen_T0_T1_T2, c);
+    bench_tagged_vec!(sum_T0_T2_T4, soa_sum_T0_T2_T4, gen_T0_T2_T4, c);
+    bench_tagged_vec!(sum_T1_T2_T3, soa_sum_T1_T2_T3, gen_T1_T2_T3, c);
+    bench_tagged_vec!(sum_T1_T3_T5, soa_sum_T1_T3_T5, gen_T1_T3_T5, c);
+    bench_tagged_vec!(count_T0_T1_T2, soa_count_T0_T1_T2, gen_T0_T1_T2, c);
+    bench_tagged_vec!(count_T0_T2_T4, soa_count_T0_T2_T4, gen_T0_T2_T4, c);
+    bench_tagged_vec!(count_T1_T2_T3, soa_count_T1_T2_T3, gen_T1_T2_T3, c);
+    bench_tagged_vec!(count_T1_T3_T5, soa_count_T1_T3_T5, gen_T1_T3_T5, c);
+
+    bench_all!(sum_T0_in_order, sum_T0, gen_T0_in_order, c);
+    bench_all!(sum_T0_shuffled, sum_T0, gen_T0_shuffled, c);
+    bench_all!(sum_T0_paged, sum_T0, gen_T0_paged, c);
+    bench_all!(sum_T0_T1_in_order, sum_T0_T1, gen_T0_T1_in_order, c);
+    bench_all!(sum_T0_T1_shuffled, sum_T0_T1, gen_T0_T1_shuffled, c);
+    bench_all!(sum_T0_T1_paged, sum_T0_T1, gen_T0_T1_paged, c);
+
     bench_all!(elide, gen_values, c);
     bench_all!(sum_chunk_t0, gen_t0_set, c);
-    bench_all!(call7, gen_t1_call7, c);
-    bench_all!(call8, gen_t1_call8, c);
+    bench_all!(call1, gen_call1, c);
+    bench_all!(call2, gen_call2, c);
+    bench_all!(call3, gen_call3, c);
+    bench_all!(call4, gen_call4, c);
+    bench_all!(call5, gen_call5, c);
+    bench_all!(call6, gen_call6, c);
+    bench_all!(call7, gen_call7, c);
+    bench_all!(call8, gen_call8, c);
+    bench_all!(call9, gen_call9, c);
+    bench_all!(call10, gen_call10, c);
+    bench_all!(call11, gen_call11, c);
+    bench_all!(call12, gen_call12, c);
+
+    bench_all!(c_sum_T0, gen_T0, c);
+    bench_all!(c_count_T1, gen_T1, c);
+    bench_all!(c_sum_T0_T1, gen_T0_T1, c);
+    bench_all!(c_count_T1_T3, gen_T1_T3, c);
+    bench_all!(c_sum_T1_T3_T5, gen_T1_T3_T5, c);
+
+    bench_all!(batch_count_T0, gen_T0, c);
+    bench_all!(batch_count_T1_T3, gen_T1_T3, c);
+    bench_all!(batch_filter_T1, gen_T1_T3, c);
+    bench_all!(batch_tags_of, gen_T1_T3_T5, c);
+
+    // Compare with count_T0_T2 and sum_T1_T3_T5. The partition_ groups include
+    // the cost of partitioning, the partitioned_ groups start from sorted data.
+    bench_all!(partition_count_T0_T2, gen_T0_T2, c);
+    bench_all!(partition_sum_T1_T3_T5, gen_T1_T3_T5, c);
+    bench_all!(partitioned_count_T0_T2, gen_T0_T2_partitioned, c);
+    bench_all!(partitioned_sum_T1_T3_T5, gen_T1_T3_T5_partitioned, c);
+
+    // Rows are the source scheme. BaseLine is a different size from the other
+    // schemes, so it has no in place row.
+    bench_all!(convert_to_baseline, gen_T0_T1_T2, c);
+    bench_all!(convert_to_low_bits, gen_T0_T1_T2, c);
+    bench_all!(convert_to_high_byte, gen_T0_T1_T2, c);
+    bench_all!(convert_to_nan_boxing, gen_T0_T1_T2, c);
+    bench_in_place!(c);
+
+    bench_all!(intern_hashmap_fx, gen_interning, c);
+    bench_all!(intern_hashmap_sip, gen_interning, c);
+    bench_all!(intern_open_fx, gen_interning, c);
+    bench_all!(intern_open_sip, gen_interning, c);
+
+    bench_all!(list_sum, gen_list, c);
+    bench_all!(tree_lookup, gen_tree, c);
+    bench_all!(hash_lookup, gen_chains, c);
+
+    bench_all!(dispatch_untag, gen_random_tags, c);
+    bench_all!(dispatch_fn_table, gen_random_tags, c);
+    bench_all!(dispatch_jump_table, gen_random_tags, c);
+    bench_all!(dispatch_branch_chain, gen_random_tags, c);
+    bench_all!(dispatch_select, gen_random_tags, c);
+    bench_all!(dispatch_untag_cyclic, dispatch_untag, gen_cyclic_tags, c);
+    bench_all!(
+        dispatch_fn_table_cyclic,
+        dispatch_fn_table,
+        gen_cyclic_tags,
+        c
+    );
+    bench_all!(
+        dispatch_jump_table_cyclic,
+        dispatch_jump_table,
+        gen_cyclic_tags,
+        c
+    );
+    bench_all!(
+        dispatch_branch_chain_cyclic,
+        dispatch_branch_chain,
+        gen_cyclic_tags,
+        c
+    );
+    bench_all!(dispatch_select_cyclic, dispatch_select, gen_cyclic_tags, c);
+
+    bench_all!(interp_fib, interpret, fib_program, c);
+    bench_all!(interp_list_sum, interpret, list_sum_program, c);
+    bench_all!(interp_tree_insert, interpret, tree_insert_program, c);
+
+    bench_all!(gc_mark_sweep, mark_sweep, gen_heap, c);
 }
 
 criterion_group!(benches, all_benches);
 
-fn main() {
-    benches();
-    Criterion::default().configure_from_args().final_summary();
-
-    let i = &X::new(13);
-    let x = LowByte::new(Basic::T1(i));
-    black_box(untag_bit0(x));
-    let i = X::new(45);
-    let x = LowByte::new(Basic::T2(&i));
-    black_box(untag_enum_2(&[x]));
-    black_box(untag_raw_2(&[x]));
-    black_box(untag_bit1(x));
-    let i = &X::new(13);
-    let x = LowBits::new(Basic::T1(i));
-    black_box(count_low(black_box(&[x])));
-    black_box(sum_repr(black_box(&[x])));
-    let x = HighBits::new(Basic::T1(i));
-    black_box(count_high(black_box(&[x])));
-}
-
-#[inline(never)]
-pub fn untag_bit0(x: LowByte<Basic>) -> i32 {
-    if let Basic::T0(x) = x.untag() {
-        unsafe { (*x).data }
-    } else {
-        13
-    }
-}
-
-#[inline(never)]
-pub fn untag_bit1(x: LowByte<Basic>) -> i32 {
-    if let Basic::T1(x) = x.untag() {
-        unsafe { (*x).data }
-    } else {
-        13
-    }
-}
-
-#[inline(never)]
-pub fn check_bit0_high(x: HighBits<Basic>) -> bool {
-    matches!(x.untag(), Basic::T0(_))
-}
-
-#[inline(never)]
-pub fn check_bit0_low(x: LowByte<Basic>) -> bool {
-    matches!(x.untag(), Basic::T0(_))
-}
-
-#[inline(never)]
-pub fn count_high(x: &[HighBits<Basic>]) -> i32 {
-    count_T0(x)
+/// Exit with an error message if a subcommand failed.
+fn exit_on_error<T>(result: std::io::Result<T>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    })
 }
 
-#[inline(never)]
-pub fn count_low(x: &[LowBits<Basic>]) -> i32 {
-    count_T0(x)
-}
-
-#[inline(never)]
-pub fn untag_enum_2(x: &[LowByte<Basic>]) -> i32 {
-    let mut sum: i32 = 0;
-    for i in x {
-        match i.untag() {
-            Basic::T1(x) => sum = sum.wrapping_add(unsafe { (*x).data }),
-            Basic::T2(x) => sum = sum.wrapping_sub(unsafe { (*x).data }),
-            _ => {}
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("asm") => return exit_on_error(asm::report()),
+        Some("hash") => return intern::report(),
+        Some("export") => {
+            let path = args.get(2).map_or("results.json", String::as_str);
+            return exit_on_error(export::export(Path::new(path)));
         }
-    }
-    sum
-}
-
-#[inline(never)]
-pub fn untag_raw_2(x: &[LowByte<Basic>]) -> i32 {
-    let mut sum: i32 = 0;
-    for i in x {
-        match i.tag() {
-            1 => sum = sum.wrapping_add(unsafe { (*i.data().cast::<X<7>>()).data }),
-            2 => sum = sum.wrapping_sub(unsafe { (*i.data().cast::<X<4>>()).data }),
-            _ => {}
+        Some("perf") => {
+            println!("seed: {} (set TAGGING_SEED to reproduce)", seed());
+            return perf::report(args.get(2).cloned(), all_benches);
         }
-    }
-    sum
-}
-
-#[inline(never)]
-pub fn untag_byte(x: LowByte<Basic>) -> i32 {
-    if let Basic::T0(x) = x.untag() {
-        unsafe { (*x).data }
-    } else {
-        13
-    }
-}
-
-#[inline(never)]
-pub fn untag_bit(x: LowBits<Basic>) -> i32 {
-    if let Basic::T0(x) = x.untag() {
-        unsafe { (*x).data }
-    } else {
-        13
-    }
-}
-
-#[inline(never)]
-pub fn sum_byte(x: &[LowByte<Basic>]) -> i32 {
-    let mut sum: i32 = 0;
-    for i in x {
-        if let Basic::T0(x) = i.untag() {
-            sum = sum.wrapping_add(unsafe { (*x).data })
+        Some("summary") => return exit_on_error(summary::summary(args.get(2).map(Path::new))),
+        Some("compare") => {
+            if exit_on_error(export::compare_files(&args[2..])) {
+                std::process::exit(1);
+            }
+            return;
         }
+        _ => {}
     }
-    sum
-}
 
-#[inline(never)]
-pub fn sum_bit(x: &[LowBits<Basic>]) -> i32 {
-    let mut sum: i32 = 0;
-    for i in x {
-        if let Basic::T0(x) = i.untag() {
-            sum = sum.wrapping_add(unsafe { (*x).data })
-        }
+    // Criterion owns the command line, so the seed is read from the environment
+    println!("seed: {} (set TAGGING_SEED to reproduce)", seed());
+    let start = SystemTime::now();
+    benches();
+    Criterion::default().configure_from_args().final_summary();
+    exit_on_error(export::record_run(&export::criterion_dir(), start, seed()));
+
+    if let Some(path) = std::env::var_os("TAGGING_EXPORT") {
+        exit_on_error(export::export(Path::new(&path)));
     }
-    sum
-}
 
-#[inline(never)]
-pub fn sum_repr(x: &[LowBits<Basic>]) -> i32 {
-    sum_T1_T3_T5(x)
+    probes::run();
 }
@@ -0,0 +1,166 @@
+// Group tagged values by tag so a pass over one variant doesn't have to check
+// the tag of every element. `partition_by_tag` is stable, values with the same
+// tag keep their relative order; `partition_in_place` is not.
+use std::ops::Range;
+
+use crate::types::*;
+
+/// Where the values with each tag ended up after partitioning.
+#[derive(Clone, Debug)]
+pub struct TagRanges {
+    /// `starts[t]..starts[t + 1]` holds the values with tag `t`
+    starts: [usize; 257],
+}
+
+impl TagRanges {
+    fn from_counts(counts: &[usize; 256]) -> Self {
+        let mut starts = [0; 257];
+        for (tag, count) in counts.iter().enumerate() {
+            starts[tag + 1] = starts[tag] + count;
+        }
+        Self { starts }
+    }
+
+    pub fn get(&self, tag: u8) -> Range<usize> {
+        self.starts[tag as usize]..self.starts[tag as usize + 1]
+    }
+}
+
+/// Values grouped by tag, see `partition_by_tag`.
+pub struct Buckets<S> {
+    values: Vec<S>,
+    ranges: TagRanges,
+}
+
+impl<S> Buckets<S> {
+    pub fn get(&self, tag: u8) -> &[S] {
+        &self.values[self.ranges.get(tag)]
+    }
+
+    pub fn values(&self) -> &[S] {
+        &self.values
+    }
+
+    pub fn ranges(&self) -> &TagRanges {
+        &self.ranges
+    }
+}
+
+fn count_tags<S: TaggedPointer<T>, T: Taggable>(x: &[S]) -> [usize; 256] {
+    let mut counts = [0; 256];
+    for value in x {
+        counts[value.tag() as usize] += 1;
+    }
+    counts
+}
+
+/// Group `x` by tag in O(n) without allocating: count the tags, then swap
+/// every value straight into the bucket for its tag.
+pub fn partition_in_place<S: TaggedPointer<T>, T: Taggable>(x: &mut [S]) -> TagRanges {
+    let ranges = TagRanges::from_counts(&count_tags(x));
+    // The next unfilled slot of each bucket
+    let mut next = ranges.starts;
+    for tag in 0..256 {
+        let end = ranges.starts[tag + 1];
+        while next[tag] < end {
+            let other = x[next[tag]].tag() as usize;
+            if other != tag {
+                x.swap(next[tag], next[other]);
+            }
+            next[other] += 1;
+        }
+    }
+    ranges
+}
+
+/// Copy `x` into buckets by tag with a counting sort.
+pub fn partition_by_tag<S: TaggedPointer<T> + Copy, T: Taggable>(x: &[S]) -> Buckets<S> {
+    let ranges = TagRanges::from_counts(&count_tags(x));
+    let mut next = ranges.starts;
+    let mut values = Vec::with_capacity(x.len());
+    let spare = values.spare_capacity_mut();
+    for &value in x {
+        let slot = &mut next[value.tag() as usize];
+        spare[*slot].write(value);
+        *slot += 1;
+    }
+    // SAFETY: the counts add up to `x.len()`, so every slot was written once
+    unsafe { values.set_len(x.len()) };
+    Buckets { values, ranges }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every bucket holds exactly the values with its tag, and `x` is a
+    /// permutation of `original`.
+    fn check<S: TaggedPointer<Basic> + Copy>(original: &[S], x: &[S], ranges: &TagRanges) {
+        let mut end = 0;
+        for tag in 0..=255 {
+            let range = ranges.get(tag);
+            assert_eq!(range.start, end, "ranges of tag {tag} aren't contiguous");
+            end = range.end;
+            assert!(
+                x[range].iter().all(|value| value.tag() == tag),
+                "wrong tag in bucket {tag}"
+            );
+        }
+        assert_eq!(end, x.len());
+        let sorted = |x: &[S]| {
+            let mut x: Vec<(u8, *const u8)> =
+                x.iter().map(|value| (value.tag(), value.data())).collect();
+            x.sort();
+            x
+        };
+        assert_eq!(sorted(x), sorted(original));
+    }
+
+    fn values<S: TaggedPointer<Basic>>(objects: &[X<0>], tags: &[u8]) -> Vec<S> {
+        tags.iter()
+            .zip(objects)
+            .map(|(&tag, x)| S::from_raw(x as *const X<0> as *const u8, tag))
+            .collect()
+    }
+
+    fn tag_patterns() -> Vec<Vec<u8>> {
+        vec![
+            vec![],
+            vec![5],
+            vec![3; 10],
+            (0..8).rev().collect(),
+            (0..100).map(|i| (i * 7 % 8) as u8).collect(),
+            (0..100).map(|i| [1, 3, 5][i % 3]).collect(),
+        ]
+    }
+
+    #[test]
+    fn partition_in_place_groups_by_tag() {
+        let objects: Vec<X<0>> = (0..100).map(X::new).collect();
+        for tags in tag_patterns() {
+            let original: Vec<LowBits<Basic>> = values(&objects, &tags);
+            let mut x = original.clone();
+            let ranges = partition_in_place(&mut x);
+            check(&original, &x, &ranges);
+        }
+    }
+
+    #[test]
+    fn partition_by_tag_groups_by_tag_and_is_stable() {
+        let objects: Vec<X<0>> = (0..100).map(X::new).collect();
+        for tags in tag_patterns() {
+            let original: Vec<BaseLine> = values(&objects, &tags);
+            let buckets = partition_by_tag(&original);
+            check(&original, buckets.values(), buckets.ranges());
+            for tag in 0..8 {
+                let expected: Vec<*const u8> = original
+                    .iter()
+                    .filter(|x| x.tag() == tag)
+                    .map(|x| x.data())
+                    .collect();
+                let actual: Vec<*const u8> = buckets.get(tag).iter().map(|x| x.data()).collect();
+                assert_eq!(actual, expected, "order within bucket {tag}");
+            }
+        }
+    }
+}
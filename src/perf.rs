@@ -0,0 +1,334 @@
+// Hardware counters for every workload and scheme, to tell extra instructions
+// apart from branch mispredicts or cache misses:
+//
+//     cargo run --release -- perf [substring of the workload names]
+//
+// Counters are opened with `perf_event_open` as one group, so they cover the
+// same stretch of execution, and only count user space, which works with the
+// default `perf_event_paranoid` of 2. Where they can't be opened, e.g. in most
+// VMs or on other platforms, only wall time is reported.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use criterion::Throughput;
+
+use crate::harness::{Group, Harness};
+
+/// Long enough to average out the cost of enabling and reading the counters.
+const MEASUREMENT: Duration = Duration::from_millis(100);
+const WARM_UP: Duration = Duration::from_millis(20);
+
+pub const EVENTS: [&str; 4] = ["instructions", "cycles", "branch-misses", "L1D misses"];
+
+/// Per iteration wall time and counts, in the order of `EVENTS`. A count is
+/// `None` if its event couldn't be opened or never got scheduled.
+pub struct Sample {
+    pub ns: f64,
+    pub counts: [Option<f64>; 4],
+}
+
+pub struct Counters {
+    events: Option<sys::EventGroup>,
+    filter: Option<String>,
+}
+
+impl Counters {
+    /// Prints why counters are unavailable, if they are, and the table header.
+    pub fn new(filter: Option<String>) -> Self {
+        let events = match sys::EventGroup::open() {
+            Ok(events) => Some(events),
+            Err(e) => {
+                eprintln!("warning: hardware counters unavailable ({e}), reporting wall time only");
+                None
+            }
+        };
+        println!("| workload | scheme | ns | {} | IPC |", EVENTS.join(" | "));
+        println!("|---|---|{}", "---:|".repeat(EVENTS.len() + 2));
+        Self { events, filter }
+    }
+
+    pub fn measure<O>(&mut self, mut routine: impl FnMut() -> O) -> Sample {
+        let start = Instant::now();
+        let mut iterations: u64 = 0;
+        while start.elapsed() < WARM_UP {
+            black_box(routine());
+            iterations += 1;
+        }
+        let per_iteration = start.elapsed().as_secs_f64() / iterations as f64;
+        let iterations = ((MEASUREMENT.as_secs_f64() / per_iteration) as u64).max(1);
+
+        if let Some(events) = &self.events {
+            events.start();
+        }
+        let start = Instant::now();
+        for _ in 0..iterations {
+            black_box(routine());
+        }
+        let elapsed = start.elapsed();
+        let counts = match &self.events {
+            Some(events) => events
+                .stop()
+                .map(|count| count.map(|x| x / iterations as f64)),
+            None => [None; 4],
+        };
+        Sample {
+            ns: elapsed.as_nanos() as f64 / iterations as f64,
+            counts,
+        }
+    }
+}
+
+pub struct CounterGroup<'a> {
+    counters: &'a mut Counters,
+    name: String,
+}
+
+impl Harness for Counters {
+    type Group<'a> = CounterGroup<'a>;
+
+    fn benchmark_group(&mut self, name: &str) -> Self::Group<'_> {
+        CounterGroup {
+            counters: self,
+            name: name.to_string(),
+        }
+    }
+}
+
+impl Group for CounterGroup<'_> {
+    // Everything is reported per iteration
+    fn throughput(&mut self, _throughput: Throughput) {}
+
+    fn bench<O>(&mut self, id: &str, routine: impl FnMut() -> O) {
+        if self
+            .counters
+            .filter
+            .as_ref()
+            .is_some_and(|filter| !self.name.contains(filter.as_str()))
+        {
+            return;
+        }
+        let sample = self.counters.measure(routine);
+        let cell = |x: Option<f64>| x.map_or("-".to_string(), |x| format!("{x:.1}"));
+        let counts: Vec<String> = sample.counts.iter().map(|&x| cell(x)).collect();
+        let ipc = match sample.counts {
+            [Some(instructions), Some(cycles), ..] if cycles > 0.0 => {
+                format!("{:.2}", instructions / cycles)
+            }
+            _ => "-".to_string(),
+        };
+        println!(
+            "| {} | {id} | {:.1} | {} | {ipc} |",
+            self.name,
+            sample.ns,
+            counts.join(" | ")
+        );
+    }
+}
+
+/// Scale a count up to the whole time the group was enabled, in case the
+/// kernel had to multiplex it with other events.
+fn scale(count: u64, enabled: u64, running: u64) -> Option<f64> {
+    (running > 0).then(|| count as f64 * enabled as f64 / running as f64)
+}
+
+#[cfg(target_os = "linux")]
+mod sys {
+    use std::fs::File;
+    use std::io::{self, Read};
+    use std::os::fd::{AsRawFd, FromRawFd};
+
+    use super::{scale, EVENTS};
+
+    const PERF_TYPE_HARDWARE: u32 = 0;
+    const PERF_TYPE_HW_CACHE: u32 = 3;
+    const PERF_COUNT_HW_CPU_CYCLES: u64 = 0;
+    const PERF_COUNT_HW_INSTRUCTIONS: u64 = 1;
+    const PERF_COUNT_HW_BRANCH_MISSES: u64 = 5;
+    // L1D, read, miss
+    const L1D_READ_MISS: u64 = 1 << 16;
+
+    const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+    const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+    const PERF_FORMAT_GROUP: u64 = 1 << 3;
+
+    const DISABLED: u64 = 1 << 0;
+    const EXCLUDE_KERNEL: u64 = 1 << 5;
+    const EXCLUDE_HV: u64 = 1 << 6;
+
+    const PERF_FLAG_FD_CLOEXEC: libc::c_ulong = 1 << 3;
+    const PERF_EVENT_IOC_ENABLE: libc::c_ulong = 0x2400;
+    const PERF_EVENT_IOC_DISABLE: libc::c_ulong = 0x2401;
+    const PERF_EVENT_IOC_RESET: libc::c_ulong = 0x2403;
+    const PERF_IOC_FLAG_GROUP: libc::c_ulong = 1;
+
+    const CONFIGS: [(u32, u64); 4] = [
+        (PERF_TYPE_HARDWARE, PERF_COUNT_HW_INSTRUCTIONS),
+        (PERF_TYPE_HARDWARE, PERF_COUNT_HW_CPU_CYCLES),
+        (PERF_TYPE_HARDWARE, PERF_COUNT_HW_BRANCH_MISSES),
+        (PERF_TYPE_HW_CACHE, L1D_READ_MISS),
+    ];
+
+    /// The first 64 bytes of `struct perf_event_attr` (`PERF_ATTR_SIZE_VER0`),
+    /// which is all that is needed here.
+    #[repr(C)]
+    #[derive(Default)]
+    struct Attr {
+        type_: u32,
+        size: u32,
+        config: u64,
+        sample_period: u64,
+        sample_type: u64,
+        read_format: u64,
+        flags: u64,
+        wakeup_events: u32,
+        bp_type: u32,
+        bp_addr: u64,
+    }
+
+    pub struct EventGroup {
+        /// The opened events and their index in `EVENTS`, leader first
+        events: Vec<(usize, File)>,
+    }
+
+    fn open(type_: u32, config: u64, group: Option<&File>) -> io::Result<File> {
+        let attr = Attr {
+            type_,
+            size: std::mem::size_of::<Attr>() as u32,
+            config,
+            read_format: PERF_FORMAT_GROUP
+                | PERF_FORMAT_TOTAL_TIME_ENABLED
+                | PERF_FORMAT_TOTAL_TIME_RUNNING,
+            flags: if group.is_none() { DISABLED } else { 0 } | EXCLUDE_KERNEL | EXCLUDE_HV,
+            ..Attr::default()
+        };
+        let group_fd = group.map_or(-1, |x| x.as_raw_fd());
+        // SAFETY: `attr` outlives the call and its `size` matches its layout
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_perf_event_open,
+                &attr as *const Attr,
+                0,
+                -1,
+                group_fd,
+                PERF_FLAG_FD_CLOEXEC,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: the kernel just gave us this descriptor
+        Ok(unsafe { File::from_raw_fd(fd as i32) })
+    }
+
+    impl EventGroup {
+        /// Open as many of `EVENTS` as the machine supports. Fails only if none
+        /// of them can be opened.
+        pub fn open() -> io::Result<Self> {
+            let mut events: Vec<(usize, File)> = Vec::new();
+            let mut errors = Vec::new();
+            for (i, &(type_, config)) in CONFIGS.iter().enumerate() {
+                match open(type_, config, events.first().map(|(_, leader)| leader)) {
+                    Ok(file) => events.push((i, file)),
+                    Err(e) => errors.push((i, e)),
+                }
+            }
+            if events.is_empty() {
+                return Err(errors.swap_remove(0).1);
+            }
+            for (i, e) in errors {
+                eprintln!("warning: can't count {}: {e}", EVENTS[i]);
+            }
+            Ok(Self { events })
+        }
+
+        fn ioctl(&self, request: libc::c_ulong) {
+            let leader = self.events[0].1.as_raw_fd();
+            // SAFETY: `leader` is an open perf event and these requests take
+            // an integer argument
+            unsafe { libc::ioctl(leader, request as _, PERF_IOC_FLAG_GROUP) };
+        }
+
+        pub fn start(&self) {
+            self.ioctl(PERF_EVENT_IOC_RESET);
+            self.ioctl(PERF_EVENT_IOC_ENABLE);
+        }
+
+        /// Stop counting and return the counts since `start`.
+        pub fn stop(&self) -> [Option<f64>; 4] {
+            self.ioctl(PERF_EVENT_IOC_DISABLE);
+            // nr, time enabled, time running, then one value per event
+            let mut buffer = [0u8; 8 * (3 + EVENTS.len())];
+            let mut counts = [None; 4];
+            let Ok(n) = (&self.events[0].1).read(&mut buffer) else {
+                return counts;
+            };
+            let words: Vec<u64> = buffer[..n]
+                .chunks_exact(8)
+                .map(|x| u64::from_ne_bytes(x.try_into().unwrap()))
+                .collect();
+            if let [_, enabled, running, values @ ..] = &words[..] {
+                for ((i, _), &value) in self.events.iter().zip(values) {
+                    counts[*i] = scale(value, *enabled, *running);
+                }
+            }
+            counts
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod sys {
+    use std::io;
+
+    pub struct EventGroup;
+
+    impl EventGroup {
+        pub fn open() -> io::Result<Self> {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "perf_event_open is Linux only",
+            ))
+        }
+
+        pub fn start(&self) {}
+
+        pub fn stop(&self) -> [Option<f64>; 4] {
+            [None; 4]
+        }
+    }
+}
+
+/// `perf [filter]`: run every workload whose name contains `filter` under the
+/// counters and print a Markdown table.
+pub fn report(filter: Option<String>, all_benches: impl FnOnce(&mut Counters)) {
+    if cfg!(debug_assertions) {
+        eprintln!("warning: this is a debug build, run with --release for meaningful counts");
+    }
+    all_benches(&mut Counters::new(filter));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn multiplexed_counts_are_scaled() {
+        assert_eq!(scale(100, 10, 10), Some(100.0));
+        assert_eq!(scale(100, 10, 5), Some(200.0));
+        assert_eq!(scale(100, 10, 0), None);
+    }
+
+    #[test]
+    fn counts_instructions_or_falls_back() {
+        let mut counters = Counters {
+            events: sys::EventGroup::open().ok(),
+            filter: None,
+        };
+        let sample = counters.measure(|| (0..1000u64).map(black_box).sum::<u64>());
+        assert!(sample.ns > 0.0);
+        // At least an add per element, if instructions can be counted here
+        if let Some(instructions) = sample.counts[0] {
+            assert!(instructions > 1000.0, "{instructions}");
+        }
+    }
+}
@@ -0,0 +1,222 @@
+// Small functions whose only purpose is to have their machine code inspected.
+// Each probe is instantiated for every scheme under a stable symbol name,
+// `probe_<probe>_<scheme>`, so it can be found in the disassembly.
+use criterion::black_box;
+
+use crate::types::*;
+
+pub const SCHEMES: &[&str] = &[
+    "baseline",
+    "low_bits",
+    "low_byte",
+    "high_bits",
+    "high_byte",
+    "nan_boxing",
+];
+
+pub const PROBES: &[&str] = &[
+    "untag_bit0",
+    "untag_bit1",
+    "check_bit0",
+    "count_t0",
+    "untag_enum_2",
+    "untag_raw_2",
+    "sum_t0",
+    "sum_repr",
+    "elide",
+    "callee_3",
+    "callee_4",
+    "callee_6",
+    "callee_7",
+    "dispatch_jump_table",
+    "dispatch_branch_chain",
+    "dispatch_select",
+];
+
+pub fn symbol(probe: &str, scheme: &str) -> String {
+    format!("probe_{probe}_{scheme}")
+}
+
+macro_rules! probes {
+    ($scheme:ident, $ty:ty) => {
+        pub mod $scheme {
+            use super::*;
+
+            #[inline(never)]
+            #[export_name = concat!("probe_untag_bit0_", stringify!($scheme))]
+            pub fn untag_bit0(x: $ty) -> i32 {
+                if let Basic::T0(x) = x.untag() {
+                    unsafe { (*x).data }
+                } else {
+                    13
+                }
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_untag_bit1_", stringify!($scheme))]
+            pub fn untag_bit1(x: $ty) -> i32 {
+                if let Basic::T1(x) = x.untag() {
+                    unsafe { (*x).data }
+                } else {
+                    13
+                }
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_check_bit0_", stringify!($scheme))]
+            pub fn check_bit0(x: $ty) -> bool {
+                matches!(x.untag(), Basic::T0(_))
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_count_t0_", stringify!($scheme))]
+            pub fn count_t0(x: &[$ty]) -> i32 {
+                crate::count_T0(x)
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_untag_enum_2_", stringify!($scheme))]
+            pub fn untag_enum_2(x: &[$ty]) -> i32 {
+                let mut sum: i32 = 0;
+                for i in x {
+                    match i.untag() {
+                        Basic::T1(x) => sum = sum.wrapping_add(unsafe { (*x).data }),
+                        Basic::T2(x) => sum = sum.wrapping_sub(unsafe { (*x).data }),
+                        _ => {}
+                    }
+                }
+                sum
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_untag_raw_2_", stringify!($scheme))]
+            pub fn untag_raw_2(x: &[$ty]) -> i32 {
+                let mut sum: i32 = 0;
+                for i in x {
+                    match i.tag() {
+                        1 => sum = sum.wrapping_add(unsafe { (*i.data().cast::<X<7>>()).data }),
+                        2 => sum = sum.wrapping_sub(unsafe { (*i.data().cast::<X<4>>()).data }),
+                        _ => {}
+                    }
+                }
+                sum
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_sum_t0_", stringify!($scheme))]
+            pub fn sum_t0(x: &[$ty]) -> i32 {
+                let mut sum: i32 = 0;
+                for i in x {
+                    if let Basic::T0(x) = i.untag() {
+                        sum = sum.wrapping_add(unsafe { (*x).data })
+                    }
+                }
+                sum
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_sum_repr_", stringify!($scheme))]
+            pub fn sum_repr(x: &[$ty]) -> i32 {
+                crate::sum_T1_T3_T5(x)
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_elide_", stringify!($scheme))]
+            pub fn elide(ptr: *const u8) -> i32 {
+                let tagged = <$ty>::from_raw(ptr, 1);
+                if let Basic::T1(x) = tagged.untag() {
+                    unsafe { (*x).data }
+                } else {
+                    13
+                }
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_callee_3_", stringify!($scheme))]
+            pub fn callee_3(a: $ty, b: $ty, c: $ty) -> $ty {
+                crate::calls::callee_3(a, b, c)
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_callee_4_", stringify!($scheme))]
+            pub fn callee_4(a: $ty, b: $ty, c: $ty, d: $ty) -> $ty {
+                crate::calls::callee_4(a, b, c, d)
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_callee_6_", stringify!($scheme))]
+            pub fn callee_6(a: $ty, b: $ty, c: $ty, d: $ty, e: $ty, f: $ty) -> $ty {
+                crate::calls::callee_6(a, b, c, d, e, f)
+            }
+
+            #[inline(never)]
+            #[allow(clippy::too_many_arguments)]
+            #[export_name = concat!("probe_callee_7_", stringify!($scheme))]
+            pub fn callee_7(a: $ty, b: $ty, c: $ty, d: $ty, e: $ty, f: $ty, g: $ty) -> $ty {
+                crate::calls::callee_7(a, b, c, d, e, f, g)
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_dispatch_jump_table_", stringify!($scheme))]
+            pub fn dispatch_jump_table(x: &[$ty]) -> i32 {
+                crate::dispatch::dispatch_jump_table(x)
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_dispatch_branch_chain_", stringify!($scheme))]
+            pub fn dispatch_branch_chain(x: &[$ty]) -> i32 {
+                crate::dispatch::dispatch_branch_chain(x)
+            }
+
+            #[inline(never)]
+            #[export_name = concat!("probe_dispatch_select_", stringify!($scheme))]
+            pub fn dispatch_select(x: &[$ty]) -> i32 {
+                crate::dispatch::dispatch_select(x)
+            }
+
+            /// Run every probe once so none of them are dropped from the binary.
+            pub fn run() {
+                // `untag_raw_2` reads a T1 value as an `X<7>`, the others read
+                // its first field
+                let x = X::<7>::new(13);
+                let ptr = &x as *const X<7> as *const u8;
+                let tagged = <$ty>::new(Basic::T1(ptr.cast()));
+                let array = [tagged];
+                let slice = black_box(&array[..]);
+                black_box(untag_bit0(black_box(tagged)));
+                black_box(untag_bit1(black_box(tagged)));
+                black_box(check_bit0(black_box(tagged)));
+                black_box(count_t0(slice));
+                black_box(untag_enum_2(slice));
+                black_box(untag_raw_2(slice));
+                black_box(sum_t0(slice));
+                black_box(sum_repr(slice));
+                black_box(elide(black_box(ptr)));
+                let t = black_box(tagged);
+                black_box(callee_3(t, t, t));
+                black_box(callee_4(t, t, t, t));
+                black_box(callee_6(t, t, t, t, t, t));
+                black_box(callee_7(t, t, t, t, t, t, t));
+                black_box(dispatch_jump_table(slice));
+                black_box(dispatch_branch_chain(slice));
+                black_box(dispatch_select(slice));
+            }
+        }
+    };
+}
+
+probes!(baseline, BaseLine);
+probes!(low_bits, LowBits<Basic>);
+probes!(low_byte, LowByte<Basic>);
+probes!(high_bits, HighBits<Basic>);
+probes!(high_byte, HighByte<Basic>);
+probes!(nan_boxing, NanBoxing<Basic>);
+
+pub fn run() {
+    baseline::run();
+    low_bits::run();
+    low_byte::run();
+    high_bits::run();
+    high_byte::run();
+    nan_boxing::run();
+}
@@ -0,0 +1,378 @@
+// Pointer free snapshots of a graph of tagged values. Pointers are replaced by
+// indices into a list of objects, so a snapshot can be written with any serde
+// format and restored into a fresh arena under a different scheme.
+//
+// Tag assignment, shared with the workloads in src/chase.rs and src/interp.rs:
+//   T0 => X<0> leaf
+//   T1 => Cons<T>
+//   T2 => Node<T>
+//   T3 => Entry<T>
+//   T6 => fixnum (immediate, stored in the pointer bits)
+//   T7 => nil (null)
+use std::collections::HashMap;
+use std::fmt;
+
+use bumpalo::Bump;
+use serde::{Deserialize, Serialize};
+
+use crate::types::*;
+
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// A pointer tag and the index of the object it points to
+    Ref(u8, u32),
+    /// A tag whose pointer bits are data rather than an address
+    Immediate(u8, u64),
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub enum Object {
+    Leaf(i32),
+    Cons {
+        car: Value,
+        cdr: Value,
+    },
+    Node {
+        key: Value,
+        left: Value,
+        right: Value,
+    },
+    Entry {
+        key: Value,
+        value: Value,
+        next: Value,
+    },
+}
+
+impl Object {
+    fn tag(&self) -> u8 {
+        match self {
+            Object::Leaf(_) => BasicTag::T0 as u8,
+            Object::Cons { .. } => BasicTag::T1 as u8,
+            Object::Node { .. } => BasicTag::T2 as u8,
+            Object::Entry { .. } => BasicTag::T3 as u8,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub objects: Vec<Object>,
+    pub roots: Vec<Value>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// A tag with no object kind or immediate assigned to it
+    UnsupportedTag(u8),
+    /// A reference to an object that isn't in the snapshot
+    DanglingRef(u32),
+    /// A reference whose tag doesn't match the kind of object it points to
+    KindMismatch { tag: u8, index: u32 },
+    /// An immediate that can't be encoded as a pointer
+    InvalidImmediate(u64),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::UnsupportedTag(tag) => write!(f, "unsupported tag {tag}"),
+            SnapshotError::DanglingRef(index) => write!(f, "reference to missing object {index}"),
+            SnapshotError::KindMismatch { tag, index } => {
+                write!(f, "object {index} doesn't match tag {tag}")
+            }
+            SnapshotError::InvalidImmediate(bits) => write!(f, "invalid immediate {bits:#x}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+fn is_immediate(tag: u8) -> bool {
+    tag == BasicTag::T6 as u8 || tag == BasicTag::T7 as u8
+}
+
+/// Walk everything reachable from `roots` and replace pointers with indices.
+/// Objects are numbered in the order they are first reached, so snapshots of
+/// the same graph are equal whatever the scheme or addresses.
+///
+/// # Safety
+/// Every pointer reachable from `roots` has to point to a live object of the
+/// kind its tag is assigned to.
+pub unsafe fn snapshot<T: TaggedPointer<Basic> + Copy>(
+    roots: &[T],
+) -> Result<Snapshot, SnapshotError> {
+    let mut walk = Walk {
+        indices: HashMap::new(),
+        pending: Vec::new(),
+    };
+    let roots = roots
+        .iter()
+        .map(|&x| walk.value(x))
+        .collect::<Result<_, _>>()?;
+    let mut objects = Vec::new();
+    // `pending` grows as new objects are reached, in index order
+    while objects.len() < walk.pending.len() {
+        let (tag, ptr) = walk.pending[objects.len()];
+        let object = match tag {
+            0 => Object::Leaf((*ptr.cast::<X<0>>()).data),
+            1 => {
+                let cons = &*ptr.cast::<Cons<T>>();
+                Object::Cons {
+                    car: walk.value(cons.car)?,
+                    cdr: walk.value(cons.cdr)?,
+                }
+            }
+            2 => {
+                let node = &*ptr.cast::<Node<T>>();
+                Object::Node {
+                    key: walk.value(node.key)?,
+                    left: walk.value(node.left)?,
+                    right: walk.value(node.right)?,
+                }
+            }
+            _ => {
+                let entry = &*ptr.cast::<Entry<T>>();
+                Object::Entry {
+                    key: walk.value(entry.key)?,
+                    value: walk.value(entry.value)?,
+                    next: walk.value(entry.next)?,
+                }
+            }
+        };
+        objects.push(object);
+    }
+    Ok(Snapshot { objects, roots })
+}
+
+struct Walk {
+    indices: HashMap<*const u8, u32>,
+    /// Every object reached so far, by index
+    pending: Vec<(u8, *const u8)>,
+}
+
+impl Walk {
+    fn value<T: TaggedPointer<Basic>>(&mut self, x: T) -> Result<Value, SnapshotError> {
+        let (tag, ptr) = (x.tag(), x.data());
+        if is_immediate(tag) {
+            return Ok(Value::Immediate(tag, ptr as u64));
+        }
+        if tag > BasicTag::T3 as u8 {
+            return Err(SnapshotError::UnsupportedTag(tag));
+        }
+        let next = self.pending.len() as u32;
+        let index = *self.indices.entry(ptr).or_insert_with(|| {
+            self.pending.push((tag, ptr));
+            next
+        });
+        Ok(Value::Ref(tag, index))
+    }
+}
+
+/// Allocate every object in `bump` and return the roots encoded with `T`.
+pub fn restore<T: TaggedPointer<Basic> + Copy>(
+    snapshot: &Snapshot,
+    bump: &Bump,
+) -> Result<Vec<T>, SnapshotError> {
+    let nil = nil();
+    // Allocate everything up front so that references can point forwards
+    let addresses: Vec<*mut X<0>> = snapshot
+        .objects
+        .iter()
+        .map(|object| match *object {
+            Object::Leaf(data) => bump.alloc(X::new(data)) as *mut X<0>,
+            Object::Cons { .. } => {
+                bump.alloc(Cons { car: nil, cdr: nil }) as *mut Cons<T> as *mut X<0>
+            }
+            Object::Node { .. } => bump.alloc(Node {
+                key: nil,
+                left: nil,
+                right: nil,
+            }) as *mut Node<T> as *mut X<0>,
+            Object::Entry { .. } => bump.alloc(Entry {
+                key: nil,
+                value: nil,
+                next: nil,
+            }) as *mut Entry<T> as *mut X<0>,
+        })
+        .collect();
+
+    let value = |value: Value| -> Result<T, SnapshotError> {
+        match value {
+            Value::Immediate(tag, bits) => {
+                if !is_immediate(tag) {
+                    return Err(SnapshotError::UnsupportedTag(tag));
+                }
+                // Immediates have to be representable by every scheme
+                if bits >= 1 << 50 || bits & 0b111 != 0 {
+                    return Err(SnapshotError::InvalidImmediate(bits));
+                }
+                Ok(T::from_raw(bits as usize as *const u8, tag))
+            }
+            Value::Ref(tag, index) => {
+                let object = snapshot
+                    .objects
+                    .get(index as usize)
+                    .ok_or(SnapshotError::DanglingRef(index))?;
+                if object.tag() != tag {
+                    return Err(SnapshotError::KindMismatch { tag, index });
+                }
+                Ok(T::new(Basic::from_raw(
+                    addresses[index as usize] as *const u8,
+                    tag,
+                )))
+            }
+        }
+    };
+
+    for (object, &address) in snapshot.objects.iter().zip(&addresses) {
+        // SAFETY: `address` was allocated above for exactly this kind of object
+        unsafe {
+            match *object {
+                Object::Leaf(_) => {}
+                Object::Cons { car, cdr } => {
+                    let cons = &mut *address.cast::<Cons<T>>();
+                    cons.car = value(car)?;
+                    cons.cdr = value(cdr)?;
+                }
+                Object::Node { key, left, right } => {
+                    let node = &mut *address.cast::<Node<T>>();
+                    node.key = value(key)?;
+                    node.left = value(left)?;
+                    node.right = value(right)?;
+                }
+                Object::Entry {
+                    key,
+                    value: val,
+                    next,
+                } => {
+                    let entry = &mut *address.cast::<Entry<T>>();
+                    entry.key = value(key)?;
+                    entry.value = value(val)?;
+                    entry.next = value(next)?;
+                }
+            }
+        }
+    }
+    snapshot.roots.iter().map(|&root| value(root)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cons<T: TaggedPointer<Basic>>(bump: &Bump, car: T, cdr: T) -> T {
+        let cell = bump.alloc(Cons { car, cdr });
+        T::new(Basic::T1(cell as *mut Cons<T> as *const X<0>))
+    }
+
+    /// A list, a tree sharing a leaf with the list, a hash chain and a cycle.
+    fn gen_graph<T: TaggedPointer<Basic> + Copy>(bump: &Bump) -> Vec<T> {
+        let shared = leaf(bump, 42);
+        let list = (0..10)
+            .rev()
+            .fold(nil(), |cdr, i| cons(bump, fixnum(i - 5), cdr));
+        let list = cons(bump, shared, list);
+        let node = |key, left, right| {
+            let node = bump.alloc(Node { key, left, right });
+            T::new(Basic::T2(node as *const Node<T> as *const X<0>))
+        };
+        let tree = node(
+            shared,
+            node(leaf(bump, 1), nil(), nil()),
+            node(fixnum(i32::MIN), nil(), nil()),
+        );
+        let entry = |key, value, next| {
+            let entry = bump.alloc(Entry { key, value, next });
+            T::new(Basic::T3(entry as *const Entry<T> as *const X<0>))
+        };
+        let chain = entry(fixnum(1), leaf(bump, -1), entry(fixnum(2), shared, nil()));
+        let cycle: T = cons(bump, fixnum(7), nil());
+        if let Basic::T1(cell) = cycle.untag() {
+            unsafe { (*cell.cast::<Cons<T>>().cast_mut()).cdr = cycle };
+        }
+        vec![list, tree, chain, cycle, fixnum(3), nil()]
+    }
+
+    fn convert<From, To>()
+    where
+        From: TaggedPointer<Basic> + Copy,
+        To: TaggedPointer<Basic> + Copy,
+    {
+        let bump = Bump::new();
+        let original = unsafe { snapshot(&gen_graph::<From>(&bump)) }.unwrap();
+        let json = serde_json::to_string(&original).unwrap();
+        let decoded: Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, original);
+
+        let restored_bump = Bump::new();
+        let roots = restore::<To>(&decoded, &restored_bump).unwrap();
+        let restored = unsafe { snapshot(&roots) }.unwrap();
+        assert_eq!(restored, original);
+    }
+
+    macro_rules! convert_to {
+        ($from:ty, $name:ident, $to:ty) => {
+            convert::<$from, $to>()
+        };
+    }
+
+    // A module per scheme, e.g. `low_bits::round_trips_to_every_scheme`
+    macro_rules! convert_tests {
+        ($name:ident, $from:ty) => {
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn round_trips_to_every_scheme() {
+                    for_each_scheme!(convert_to!($from,));
+                }
+            }
+        };
+    }
+
+    for_each_scheme!(convert_tests!());
+
+    #[test]
+    fn shared_objects_are_written_once() {
+        let bump = Bump::new();
+        let shared: LowBits<Basic> = leaf(&bump, 42);
+        let list = cons(&bump, shared, cons(&bump, shared, nil()));
+        let snapshot = unsafe { snapshot(&[list, shared]) }.unwrap();
+        assert_eq!(snapshot.objects.len(), 3);
+        assert_eq!(snapshot.roots[1], Value::Ref(BasicTag::T0 as u8, 1));
+    }
+
+    #[test]
+    fn restore_rejects_bad_snapshots() {
+        let bump = Bump::new();
+        let restore = |objects: Vec<Object>, root| {
+            restore::<LowBits<Basic>>(
+                &Snapshot {
+                    objects,
+                    roots: vec![root],
+                },
+                &bump,
+            )
+            .err()
+        };
+        let leaf = || vec![Object::Leaf(1)];
+        assert_eq!(
+            restore(leaf(), Value::Ref(0, 1)),
+            Some(SnapshotError::DanglingRef(1))
+        );
+        assert_eq!(
+            restore(leaf(), Value::Ref(1, 0)),
+            Some(SnapshotError::KindMismatch { tag: 1, index: 0 })
+        );
+        assert_eq!(
+            restore(leaf(), Value::Immediate(0, 8)),
+            Some(SnapshotError::UnsupportedTag(0))
+        );
+        assert_eq!(
+            restore(leaf(), Value::Immediate(6, 1 << 60)),
+            Some(SnapshotError::InvalidImmediate(1 << 60))
+        );
+        assert_eq!(restore(leaf(), Value::Ref(0, 0)), None);
+    }
+}
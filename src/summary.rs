@@ -0,0 +1,270 @@
+// Every workload against every scheme in one table, as time relative to
+// `BaseLine`, with the schemes ranked by the geometric mean over workloads.
+//
+//     cargo run --release -- summary              # Markdown to stdout
+//     cargo run --release -- summary summary.html
+use std::fmt::Write;
+use std::io;
+use std::path::Path;
+
+use crate::export::{criterion_dir, load_run, scheme_order, Row};
+
+pub struct Matrix {
+    /// In `SCHEMES` order, then any other rows such as `tagged_vec`
+    pub schemes: Vec<String>,
+    /// One row per workload, with a cell per scheme
+    pub workloads: Vec<(String, Vec<Option<f64>>)>,
+    /// Scheme index and geometric mean over every workload, fastest first
+    pub ranking: Vec<(usize, f64)>,
+    /// Schemes missing some workloads: index, geometric mean over the ones it
+    /// has and how many that is. Not comparable with `ranking` or each other.
+    pub partial: Vec<(usize, f64, usize)>,
+}
+
+impl Matrix {
+    /// Workloads without a `baseline` row have nothing to be relative to and
+    /// are left out.
+    pub fn new(rows: &[Row]) -> Self {
+        let mut schemes: Vec<String> = rows.iter().map(|row| row.scheme.clone()).collect();
+        schemes.sort_by(|a, b| scheme_order(a).cmp(&scheme_order(b)));
+        schemes.dedup();
+
+        let mut workloads: Vec<(String, Vec<Option<f64>>)> = Vec::new();
+        for row in rows {
+            let Some(relative) = row.relative else {
+                continue;
+            };
+            if workloads
+                .last()
+                .is_none_or(|(workload, _)| *workload != row.workload)
+            {
+                workloads.push((row.workload.clone(), vec![None; schemes.len()]));
+            }
+            let i = schemes.iter().position(|x| *x == row.scheme).unwrap();
+            workloads.last_mut().unwrap().1[i] = Some(relative);
+        }
+
+        // Every workload has a baseline row, so only schemes that ran all of
+        // them can be compared by their means
+        let mut ranking = Vec::new();
+        let mut partial = Vec::new();
+        for i in 0..schemes.len() {
+            let cells: Vec<f64> = workloads.iter().filter_map(|(_, cells)| cells[i]).collect();
+            if cells.is_empty() {
+                continue;
+            }
+            let geomean = (cells.iter().map(|x| x.ln()).sum::<f64>() / cells.len() as f64).exp();
+            if cells.len() == workloads.len() {
+                ranking.push((i, geomean));
+            } else {
+                partial.push((i, geomean, cells.len()));
+            }
+        }
+        ranking.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+        Self {
+            schemes,
+            workloads,
+            ranking,
+            partial,
+        }
+    }
+
+    /// The fastest scheme of a workload, to be highlighted.
+    fn best(cells: &[Option<f64>]) -> Option<usize> {
+        (0..cells.len())
+            .filter(|&i| cells[i].is_some())
+            .min_by(|&a, &b| cells[a].unwrap().total_cmp(&cells[b].unwrap()))
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "# Ranking over {} workloads\n", self.workloads.len()).unwrap();
+        writeln!(out, "| rank | scheme | geomean |").unwrap();
+        writeln!(out, "|---:|---|---:|").unwrap();
+        for (rank, &(i, geomean)) in self.ranking.iter().enumerate() {
+            writeln!(out, "| {} | {} | {geomean:.3} |", rank + 1, self.schemes[i]).unwrap();
+        }
+        if !self.partial.is_empty() {
+            writeln!(out, "\n# Not ranked, missing workloads\n").unwrap();
+            writeln!(out, "| scheme | geomean | workloads |").unwrap();
+            writeln!(out, "|---|---:|---:|").unwrap();
+            for &(i, geomean, n) in &self.partial {
+                writeln!(
+                    out,
+                    "| {} | {geomean:.3} | {n} of {} |",
+                    self.schemes[i],
+                    self.workloads.len()
+                )
+                .unwrap();
+            }
+        }
+
+        writeln!(out, "\n# Time relative to baseline\n").unwrap();
+        write!(out, "| workload |").unwrap();
+        for scheme in &self.schemes {
+            write!(out, " {scheme} |").unwrap();
+        }
+        write!(out, "\n|---|").unwrap();
+        for _ in &self.schemes {
+            write!(out, "---:|").unwrap();
+        }
+        writeln!(out).unwrap();
+        for (workload, cells) in &self.workloads {
+            let best = Self::best(cells);
+            write!(out, "| {workload} |").unwrap();
+            for (i, cell) in cells.iter().enumerate() {
+                match cell {
+                    Some(x) if Some(i) == best => write!(out, " **{x:.2}** |").unwrap(),
+                    Some(x) => write!(out, " {x:.2} |").unwrap(),
+                    None => write!(out, " - |").unwrap(),
+                }
+            }
+            writeln!(out).unwrap();
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "<h2>Ranking over {} workloads</h2>\n<table>",
+            self.workloads.len()
+        )
+        .unwrap();
+        writeln!(out, "<tr><th>rank</th><th>scheme</th><th>geomean</th></tr>").unwrap();
+        for (rank, &(i, geomean)) in self.ranking.iter().enumerate() {
+            writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{geomean:.3}</td></tr>",
+                rank + 1,
+                self.schemes[i]
+            )
+            .unwrap();
+        }
+        writeln!(out, "</table>").unwrap();
+        if !self.partial.is_empty() {
+            writeln!(out, "<h2>Not ranked, missing workloads</h2>\n<table>").unwrap();
+            writeln!(
+                out,
+                "<tr><th>scheme</th><th>geomean</th><th>workloads</th></tr>"
+            )
+            .unwrap();
+            for &(i, geomean, n) in &self.partial {
+                let total = self.workloads.len();
+                writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{geomean:.3}</td><td>{n} of {total}</td></tr>",
+                    self.schemes[i]
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+        }
+        writeln!(out, "<h2>Time relative to baseline</h2>\n<table>").unwrap();
+        write!(out, "<tr><th>workload</th>").unwrap();
+        for scheme in &self.schemes {
+            write!(out, "<th>{scheme}</th>").unwrap();
+        }
+        writeln!(out, "</tr>").unwrap();
+        for (workload, cells) in &self.workloads {
+            let best = Self::best(cells);
+            write!(out, "<tr><td>{workload}</td>").unwrap();
+            for (i, cell) in cells.iter().enumerate() {
+                match cell {
+                    Some(x) if Some(i) == best => write!(out, "<td><b>{x:.2}</b></td>").unwrap(),
+                    Some(x) => write!(out, "<td>{x:.2}</td>").unwrap(),
+                    None => write!(out, "<td>-</td>").unwrap(),
+                }
+            }
+            writeln!(out, "</tr>").unwrap();
+        }
+        writeln!(out, "</table>").unwrap();
+        out
+    }
+}
+
+/// Print the summary of the last run as Markdown, or write it to `path`
+/// as HTML if it ends in `.html` and as Markdown otherwise.
+pub fn summary(path: Option<&Path>) -> io::Result<()> {
+    let matrix = Matrix::new(&load_run(&criterion_dir())?.rows);
+    match path {
+        None => print!("{}", matrix.to_markdown()),
+        Some(path) if path.extension().is_some_and(|x| x == "html") => {
+            std::fs::write(path, matrix.to_html())?
+        }
+        Some(path) => std::fs::write(path, matrix.to_markdown())?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(workload: &str, scheme: &str, relative: Option<f64>) -> Row {
+        Row {
+            workload: workload.into(),
+            scheme: scheme.into(),
+            size: None,
+            median_ns: 1.0,
+            median_lower_ns: 1.0,
+            median_upper_ns: 1.0,
+            throughput: None,
+            relative,
+        }
+    }
+
+    #[test]
+    fn ranks_schemes_by_geometric_mean() {
+        let rows = [
+            row("count_T0", "baseline", Some(1.0)),
+            row("count_T0", "low_bits", Some(0.5)),
+            row("count_T0", "high_byte", Some(2.0)),
+            row("convert_in_place_round_trip", "low_bits", None),
+            row("sum_T0", "baseline", Some(1.0)),
+            row("sum_T0", "low_bits", Some(2.0)),
+            row("sum_T0", "high_byte", Some(0.125)),
+            row("sum_T0", "tagged_vec", Some(0.9)),
+        ];
+        let matrix = Matrix::new(&rows);
+        assert_eq!(
+            matrix.schemes,
+            ["baseline", "low_bits", "high_byte", "tagged_vec"]
+        );
+        assert_eq!(matrix.workloads.len(), 2);
+        assert_eq!(
+            matrix.workloads[0].1,
+            [Some(1.0), Some(0.5), Some(2.0), None]
+        );
+        // tagged_vec only ran sum_T0, so it isn't ranked against the others
+        let ranking: Vec<(usize, String)> = matrix
+            .ranking
+            .iter()
+            .map(|&(i, geomean)| (i, format!("{geomean:.3}")))
+            .collect();
+        assert_eq!(
+            ranking,
+            [
+                (2, "0.500".into()),
+                (0, "1.000".into()),
+                (1, "1.000".into())
+            ]
+        );
+        let partial: Vec<(usize, String, usize)> = matrix
+            .partial
+            .iter()
+            .map(|&(i, geomean, n)| (i, format!("{geomean:.3}"), n))
+            .collect();
+        assert_eq!(partial, [(3, "0.900".into(), 1)]);
+
+        let markdown = matrix.to_markdown();
+        assert!(markdown.contains("| 1 | high_byte | 0.500 |"));
+        assert!(markdown.contains("| tagged_vec | 0.900 | 1 of 2 |"));
+        assert!(markdown.contains("| count_T0 | 1.00 | **0.50** | 2.00 | - |"));
+        assert!(matrix.to_html().contains(
+            "<tr><td>sum_T0</td><td>1.00</td><td>2.00</td><td><b>0.12</b></td><td>0.90</td></tr>"
+        ));
+    }
+}
@@ -0,0 +1,131 @@
+// `match value.untag() { .. }` rebuilds the enum and then matches on it, which
+// LLVM doesn't always see through. `tagged_match!` takes the same arms but
+// matches on `tag()` directly.
+
+/// Match on a tagged value as if it was untagged:
+///
+/// ```
+/// use tagging::tagged_match;
+/// use tagging::types::*;
+///
+/// let x = X::new(13);
+/// let value = LowBits::<Basic>::new(Basic::T3(&x));
+/// let data = tagged_match!(value, {
+///     Basic::T1(x) => unsafe { (*x).data },
+///     Basic::T3(x) => unsafe { (*x).data },
+///     _ => 0,
+/// });
+/// assert_eq!(data, 13);
+/// ```
+///
+/// `value` can be any `TaggedPointer`. The enum has to be `repr(u8)` with
+/// every variant holding a single pointer, and the discriminant of each
+/// variant has to be its tag, as for `Basic`. Each binding is `data()` cast to
+/// the pointer type of its variant. The `_` arm is required.
+#[macro_export]
+macro_rules! tagged_match {
+    ($value:expr, {
+        $($enum:ident :: $variant:ident ($binding:pat) => $arm:expr,)*
+        _ => $default:expr $(,)?
+    }) => {{
+        // For callers that don't have the trait in scope
+        #[allow(unused_imports)]
+        use $crate::types::TaggedPointer as _;
+        let value = &$value;
+        $(
+            // Named after the variant so the arms below can use it as a pattern
+            #[allow(non_upper_case_globals)]
+            const $variant: u8 = unsafe { $crate::tagged_match::discriminant(&$enum::$variant(::std::ptr::null())) };
+        )*
+        match value.tag() {
+            $($variant => {
+                let $binding = $crate::tagged_match::payload($enum::$variant, value.data());
+                $arm
+            })*
+            _ => $default,
+        }
+    }};
+}
+
+/// The discriminant of a `repr(u8)` enum.
+///
+/// # Safety
+/// `E` has to be a `repr(u8)` enum.
+#[doc(hidden)]
+pub const unsafe fn discriminant<E: Copy>(value: &E) -> u8 {
+    *(value as *const E).cast::<u8>()
+}
+
+/// Cast `ptr` to the pointer type held by `variant`.
+#[doc(hidden)]
+#[inline(always)]
+pub fn payload<P, E>(_variant: fn(*const P) -> E, ptr: *const u8) -> *const P {
+    ptr.cast()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::types::*;
+
+    fn untag_all<S: TaggedPointer<Basic>>(value: &S) -> (u8, *const X<0>) {
+        match value.untag() {
+            Basic::T0(x) => (0, x),
+            Basic::T1(x) => (1, x),
+            Basic::T2(x) => (2, x),
+            Basic::T3(x) => (3, x),
+            Basic::T4(x) => (4, x),
+            Basic::T5(x) => (5, x),
+            Basic::T6(x) => (6, x),
+            Basic::T7(x) => (7, x),
+        }
+    }
+
+    fn tagged_match_all<S: TaggedPointer<Basic>>(value: &S) -> (u8, *const X<0>) {
+        tagged_match!(value, {
+            Basic::T0(x) => (0, x),
+            Basic::T1(x) => (1, x),
+            Basic::T2(x) => (2, x),
+            Basic::T3(x) => (3, x),
+            Basic::T4(x) => (4, x),
+            Basic::T5(x) => (5, x),
+            Basic::T6(x) => (6, x),
+            Basic::T7(x) => (7, x),
+            _ => unreachable!(),
+        })
+    }
+
+    fn untag_some<S: TaggedPointer<Basic>>(value: &S) -> i32 {
+        match value.untag() {
+            Basic::T1(x) => unsafe { (*x).data },
+            Basic::T3(x) => unsafe { -(*x).data },
+            _ => 0,
+        }
+    }
+
+    fn tagged_match_some<S: TaggedPointer<Basic>>(value: &S) -> i32 {
+        tagged_match!(value, {
+            Basic::T1(x) => unsafe { (*x).data },
+            Basic::T3(x) => unsafe { -(*x).data },
+            _ => 0,
+        })
+    }
+
+    fn matches_untag<S: TaggedPointer<Basic>>() {
+        let x = X::new(13);
+        for tag in 0..8 {
+            let value = S::new(Basic::from_raw(&x as *const X<0> as *const u8, tag));
+            assert_eq!(tagged_match_all(&value), untag_all(&value), "tag {tag}");
+            assert_eq!(tagged_match_some(&value), untag_some(&value), "tag {tag}");
+        }
+    }
+
+    #[test]
+    fn tagged_match_matches_untag() {
+        matches_untag::<BaseLine>();
+        matches_untag::<LowBits<Basic>>();
+        matches_untag::<LowByte<Basic>>();
+        matches_untag::<HighBits<Basic>>();
+        matches_untag::<HighByte<Basic>>();
+        matches_untag::<NanBoxing<Basic>>();
+    }
+}
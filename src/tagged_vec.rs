@@ -0,0 +1,199 @@
+// Struct of arrays storage for tagged values. Instead of packing the tag into
+// every pointer, the tags and pointers are kept in two parallel columns.
+use std::{
+    iter::{FusedIterator, Zip},
+    marker::PhantomData,
+    slice,
+};
+
+use crate::types::Taggable;
+
+pub struct TaggedVec<T: Taggable> {
+    tags: Vec<u8>,
+    ptrs: Vec<*const u8>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Taggable> TaggedVec<T> {
+    pub fn new() -> Self {
+        Self::with_capacity(0)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            tags: Vec::with_capacity(capacity),
+            ptrs: Vec::with_capacity(capacity),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn push(&mut self, val: T) {
+        self.tags.push(val.tag());
+        self.ptrs.push(val.ptr());
+    }
+
+    #[inline(always)]
+    pub fn get(&self, index: usize) -> Option<T> {
+        let tag = *self.tags.get(index)?;
+        Some(T::from_raw(self.ptrs[index], tag))
+    }
+
+    pub fn len(&self) -> usize {
+        self.tags.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tags.is_empty()
+    }
+
+    pub fn tags(&self) -> &[u8] {
+        &self.tags
+    }
+
+    pub fn ptrs(&self) -> &[*const u8] {
+        &self.ptrs
+    }
+
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            inner: self.tags.iter().zip(self.ptrs.iter()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Taggable> Default for TaggedVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Taggable> Clone for TaggedVec<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tags: self.tags.clone(),
+            ptrs: self.ptrs.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Taggable> Extend<T> for TaggedVec<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        let (lower, _) = iter.size_hint();
+        self.tags.reserve(lower);
+        self.ptrs.reserve(lower);
+        for val in iter {
+            self.push(val);
+        }
+    }
+}
+
+impl<T: Taggable> FromIterator<T> for TaggedVec<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        let mut vec = Self::new();
+        vec.extend(iter);
+        vec
+    }
+}
+
+impl<'a, T: Taggable> IntoIterator for &'a TaggedVec<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Rebuilds each value from its two columns with `Taggable::from_raw`.
+pub struct Iter<'a, T> {
+    inner: Zip<slice::Iter<'a, u8>, slice::Iter<'a, *const u8>>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Taggable> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    #[inline(always)]
+    fn next(&mut self) -> Option<T> {
+        let (&tag, &ptr) = self.inner.next()?;
+        Some(T::from_raw(ptr, tag))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T: Taggable> ExactSizeIterator for Iter<'_, T> {}
+
+impl<T: Taggable> FusedIterator for Iter<'_, T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Basic, X};
+
+    /// Every variant, then a run of mixed tags.
+    fn values(objects: &[X<0>]) -> Vec<Basic> {
+        let tags = (0..8).chain([3, 3, 0, 7, 1, 6, 1]);
+        tags.zip(objects)
+            .map(|(tag, x)| Basic::from_raw(x as *const X<0> as *const u8, tag))
+            .collect()
+    }
+
+    fn check(vec: &TaggedVec<Basic>, expected: &[Basic]) {
+        assert_eq!(vec.len(), expected.len());
+        assert_eq!(vec.is_empty(), expected.is_empty());
+        let tags: Vec<u8> = expected.iter().map(|x| x.tag()).collect();
+        let ptrs: Vec<*const u8> = expected.iter().map(|x| x.ptr()).collect();
+        assert_eq!(vec.tags(), tags);
+        assert_eq!(vec.ptrs(), ptrs);
+        for (i, x) in expected.iter().enumerate() {
+            assert_eq!(vec.get(i), Some(*x), "index {i}");
+        }
+        assert_eq!(vec.get(expected.len()), None);
+        assert_eq!(vec.iter().len(), expected.len());
+        assert_eq!(vec.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(vec.into_iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn empty_vec_has_no_values() {
+        check(&TaggedVec::new(), &[]);
+        check(&TaggedVec::default(), &[]);
+        check(&[].into_iter().collect(), &[]);
+        let vec = TaggedVec::<Basic>::new();
+        let mut iter = vec.iter();
+        assert_eq!(iter.next(), None);
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn push_keeps_every_variant() {
+        let objects: Vec<X<0>> = (0..15).map(X::new).collect();
+        let values = values(&objects);
+        let mut vec = TaggedVec::with_capacity(4);
+        for (i, &x) in values.iter().enumerate() {
+            vec.push(x);
+            check(&vec, &values[..=i]);
+        }
+        check(&vec.clone(), &values);
+    }
+
+    #[test]
+    fn extend_and_collect_match_push() {
+        let objects: Vec<X<0>> = (0..15).map(X::new).collect();
+        let values = values(&objects);
+        check(&values.iter().copied().collect(), &values);
+
+        let (front, back) = values.split_at(5);
+        let mut vec: TaggedVec<Basic> = front.iter().copied().collect();
+        vec.extend(back.iter().copied());
+        check(&vec, &values);
+        vec.extend([]);
+        check(&vec, &values);
+    }
+}
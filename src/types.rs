@@ -1,11 +1,45 @@
 use std::{hint::unreachable_unchecked, marker::PhantomData};
 
+use bumpalo::Bump;
+
 pub trait Taggable {
     fn tag(&self) -> u8;
     fn ptr(&self) -> *const u8;
+    /// `tag` has to be valid, see `is_valid_tag`.
     fn from_raw(ptr: *const u8, tag: u8) -> Self;
+    fn is_valid_tag(tag: u8) -> bool;
+
+    fn try_from_raw(ptr: *const u8, tag: u8) -> Result<Self, DecodeError>
+    where
+        Self: Sized,
+    {
+        if Self::is_valid_tag(tag) {
+            Ok(Self::from_raw(ptr, tag))
+        } else {
+            Err(DecodeError::InvalidTag(tag))
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The tag doesn't belong to any variant
+    InvalidTag(u8),
+    /// The word isn't a tagged pointer at all, e.g. a double when NaN boxing
+    NotTagged,
+}
+
+impl std::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::InvalidTag(tag) => write!(f, "invalid tag {tag}"),
+            DecodeError::NotTagged => write!(f, "not a tagged pointer"),
+        }
+    }
 }
 
+impl std::error::Error for DecodeError {}
+
 pub trait TaggedPointer<T: Taggable>
 where
     Self: Sized,
@@ -21,6 +55,14 @@ where
     fn from_raw(ptr: *const u8, tag: u8) -> Self;
     fn tag(&self) -> u8;
     fn data(&self) -> *const u8;
+
+    /// Equality on what the values point to. `PartialEq` only compares the
+    /// encoded words, this calls `pointee_eq` for values with the same tag but
+    /// different addresses.
+    fn structural_eq(&self, other: &Self, pointee_eq: impl Fn(T, T) -> bool) -> bool {
+        self.tag() == other.tag()
+            && (self.data() == other.data() || pointee_eq(self.untag(), other.untag()))
+    }
 }
 
 // Define a type with different offsets for the primary field
@@ -46,8 +88,30 @@ impl<const N: usize> X<N> {
     }
 }
 
+// Heap objects whose fields are themselves tagged values. They are stored
+// behind the `Basic` pointer and cast back based on the tag.
+#[repr(C, align(8))]
+pub struct Cons<T> {
+    pub car: T,
+    pub cdr: T,
+}
+
+#[repr(C, align(8))]
+pub struct Node<T> {
+    pub key: T,
+    pub left: T,
+    pub right: T,
+}
+
+#[repr(C, align(8))]
+pub struct Entry<T> {
+    pub key: T,
+    pub value: T,
+    pub next: T,
+}
+
 #[repr(u8)]
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Basic {
     // T0(*const X<0>) = 0,
     // T1(*const X<7>) = 1,
@@ -79,6 +143,27 @@ pub enum BasicTag {
     T7 = 7,
 }
 
+// Values under the tag assignment shared by the graph workloads and
+// `snapshot.rs`.
+
+/// A `T0` leaf holding `data`, allocated in `bump`.
+#[inline(always)]
+pub fn leaf<T: TaggedPointer<Basic>>(bump: &Bump, data: i32) -> T {
+    T::new(Basic::T0(bump.alloc(X::new(data))))
+}
+
+/// A `T6` immediate, `n` shifted above the bits a low tag can use.
+#[inline(always)]
+pub fn fixnum<T: TaggedPointer<Basic>>(n: i32) -> T {
+    T::new(Basic::T6(((n as u32 as usize) << 3) as *const X<0>))
+}
+
+/// `T7` with a null pointer.
+#[inline(always)]
+pub fn nil<T: TaggedPointer<Basic>>() -> T {
+    T::new(Basic::T7(std::ptr::null()))
+}
+
 impl Taggable for Basic {
     #[inline(always)]
     fn tag(&self) -> u8 {
@@ -130,8 +215,13 @@ impl Taggable for Basic {
             _ => unsafe { unreachable_unchecked() },
         }
     }
+
+    fn is_valid_tag(tag: u8) -> bool {
+        tag <= BasicTag::T7 as u8
+    }
 }
 
+#[repr(transparent)]
 #[derive(Copy, Clone)]
 pub struct LowBits<T> {
     data: *const u8,
@@ -177,6 +267,7 @@ impl<T: Taggable> TaggedPointer<T> for LowBits<T> {
     }
 }
 
+#[repr(transparent)]
 #[derive(Copy, Clone)]
 pub struct LowByte<T> {
     data: *const u8,
@@ -208,6 +299,7 @@ impl<T: Taggable> TaggedPointer<T> for LowByte<T> {
     }
 }
 
+#[repr(transparent)]
 #[derive(Copy, Clone)]
 pub struct HighBits<T> {
     data: *const u8,
@@ -244,6 +336,7 @@ impl<T: Taggable> TaggedPointer<T> for HighBits<T> {
     }
 }
 
+#[repr(transparent)]
 #[derive(Copy, Clone)]
 pub struct HighByte<T> {
     data: *const u8,
@@ -280,7 +373,8 @@ impl<T: Taggable> TaggedPointer<T> for HighByte<T> {
     }
 }
 
-#[derive(Copy, Clone)]
+#[repr(transparent)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub struct BaseLine {
     data: Basic,
 }
@@ -307,6 +401,7 @@ impl TaggedPointer<Basic> for BaseLine {
     }
 }
 
+#[repr(transparent)]
 #[derive(Copy, Clone)]
 pub struct NanBoxing<T> {
     data: *const u8,
@@ -345,3 +440,305 @@ impl<T: Taggable> TaggedPointer<T> for NanBoxing<T> {
         (self.data as usize & !Self::MASK) as *const u8
     }
 }
+
+/// Checked decoding of words that didn't come from `from_raw`, e.g. ones read
+/// back from a snapshot. `from_raw` and `untag` assume their input is valid.
+pub trait FromBits<T: Taggable>: TaggedPointer<T> {
+    fn from_bits(bits: usize) -> Result<Self, DecodeError>;
+    fn to_bits(&self) -> usize;
+
+    fn try_untag(bits: usize) -> Result<T, DecodeError> {
+        Ok(Self::from_bits(bits)?.untag())
+    }
+}
+
+macro_rules! from_bits {
+    ($scheme:ident, |$bits:ident| $is_tagged:expr) => {
+        impl<T: Taggable> FromBits<T> for $scheme<T> {
+            fn from_bits($bits: usize) -> Result<Self, DecodeError> {
+                if !$is_tagged {
+                    return Err(DecodeError::NotTagged);
+                }
+                let x = Self {
+                    data: $bits as *const u8,
+                    tag_type: PhantomData,
+                };
+                match x.tag() {
+                    tag if T::is_valid_tag(tag) => Ok(x),
+                    tag => Err(DecodeError::InvalidTag(tag)),
+                }
+            }
+
+            fn to_bits(&self) -> usize {
+                self.data as usize
+            }
+        }
+    };
+}
+
+from_bits!(LowBits, |_bits| true);
+from_bits!(LowByte, |_bits| true);
+from_bits!(HighBits, |_bits| true);
+from_bits!(HighByte, |_bits| true);
+// `from_raw` never sets the sign bit
+from_bits!(NanBoxing, |bits| bits & (1 << 63 | Self::QNAN)
+    == Self::QNAN);
+
+impl std::fmt::Debug for BaseLine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BaseLine")
+            .field("tag", &self.tag())
+            .field("ptr", &self.data())
+            .finish()
+    }
+}
+
+// Derives would require `T: PartialEq` etc. because of the `PhantomData`, and
+// compare the encoded word anyway, so these are written out by hand.
+macro_rules! word_traits {
+    ($scheme:ident) => {
+        impl<T: Taggable> std::fmt::Debug for $scheme<T> {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.debug_struct(stringify!($scheme))
+                    .field("tag", &self.tag())
+                    .field("ptr", &self.data())
+                    .finish()
+            }
+        }
+
+        /// Identity: equal when the encoded words are.
+        impl<T> PartialEq for $scheme<T> {
+            fn eq(&self, other: &Self) -> bool {
+                self.data == other.data
+            }
+        }
+
+        impl<T> Eq for $scheme<T> {}
+
+        impl<T> std::hash::Hash for $scheme<T> {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                (self.data as usize).hash(state);
+            }
+        }
+    };
+}
+
+word_traits!(LowBits);
+word_traits!(LowByte);
+word_traits!(HighBits);
+word_traits!(HighByte);
+word_traits!(NanBoxing);
+
+/// Invoke `$m!(.., name, Scheme)` for every scheme, `name` being its snake
+/// case name, to run generic tests over all of them.
+#[cfg(test)]
+macro_rules! for_each_scheme {
+    ($m:ident!($($args:tt)*)) => {
+        $m!($($args)* baseline, BaseLine);
+        $m!($($args)* low_bits, LowBits<Basic>);
+        $m!($($args)* low_byte, LowByte<Basic>);
+        $m!($($args)* high_bits, HighBits<Basic>);
+        $m!($($args)* high_byte, HighByte<Basic>);
+        $m!($($args)* nan_boxing, NanBoxing<Basic>);
+    };
+}
+
+#[cfg(test)]
+pub(crate) use for_each_scheme;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use proptest::prelude::*;
+
+    use super::*;
+
+    /// The top of the user address space with 4 and 5 level paging on x86-64.
+    const TOP_47: usize = (1 << 47) - 1;
+    const TOP_56: usize = (1 << 56) - 1;
+
+    /// Addresses up to `max` aligned to `align`, biased towards both ends of
+    /// the range and the top of the 47 bit address space.
+    fn addresses(max: usize, align: usize) -> impl Strategy<Value = usize> {
+        prop_oneof![
+            0..=max,
+            0..=4096usize,
+            max - 4096..=max,
+            TOP_47 - 4096..=TOP_47,
+        ]
+        .prop_map(move |addr| addr & !(align - 1))
+    }
+
+    fn round_trip<S: TaggedPointer<Basic>>(addr: usize, tag: u8) {
+        let ptr = addr as *const u8;
+        let x = S::from_raw(ptr, tag);
+        assert_eq!(x.tag(), tag, "tag of ({addr:#x}, {tag})");
+        assert_eq!(x.data(), ptr, "data of ({addr:#x}, {tag})");
+        assert_eq!(
+            x.untag(),
+            Basic::from_raw(ptr, tag),
+            "untag of ({addr:#x}, {tag})"
+        );
+        let y = S::new(x.untag());
+        assert_eq!((y.tag(), y.data()), (tag, ptr), "new of ({addr:#x}, {tag})");
+    }
+
+    macro_rules! round_trip_tests {
+        ($($name:ident: $scheme:ty, $max:expr, $align:expr;)*) => {
+            proptest! {
+                $(
+                    #[test]
+                    fn $name(addr in addresses($max, $align), tag in 0..8u8) {
+                        round_trip::<$scheme>(addr, tag);
+                    }
+                )*
+            }
+
+            #[test]
+            fn edge_addresses_round_trip() {
+                $(
+                    for addr in [0, $align, TOP_47, $max] {
+                        for tag in 0..8 {
+                            round_trip::<$scheme>(addr & !($align - 1), tag);
+                        }
+                    }
+                )*
+            }
+        };
+    }
+
+    round_trip_tests! {
+        baseline_round_trips: BaseLine, usize::MAX, 1;
+        low_bits_round_trips: LowBits<Basic>, usize::MAX, 8;
+        low_byte_round_trips: LowByte<Basic>, TOP_56, 1;
+        high_bits_round_trips: HighBits<Basic>, (1 << 61) - 1, 8;
+        high_byte_round_trips: HighByte<Basic>, TOP_56, 1;
+        nan_boxing_round_trips: NanBoxing<Basic>, (1 << 50) - 1, 8;
+    }
+
+    macro_rules! decode_tests {
+        ($($name:ident: $scheme:ty;)*) => {
+            proptest! {
+                $(
+                    #[test]
+                    fn $name(bits in any::<usize>()) {
+                        // Whatever decodes has to encode back to the same word
+                        if let Ok(basic) = <$scheme>::try_untag(bits) {
+                            prop_assert_eq!(<$scheme>::new(basic).to_bits(), bits);
+                        }
+                    }
+                )*
+            }
+        };
+    }
+
+    decode_tests! {
+        low_bits_decodes_any_word: LowBits<Basic>;
+        low_byte_decodes_any_word: LowByte<Basic>;
+        high_bits_decodes_any_word: HighBits<Basic>;
+        high_byte_decodes_any_word: HighByte<Basic>;
+        nan_boxing_decodes_any_word: NanBoxing<Basic>;
+    }
+
+    #[test]
+    fn decoding_rejects_invalid_words() {
+        assert_eq!(
+            LowByte::<Basic>::try_untag(0x1008).unwrap_err(),
+            DecodeError::InvalidTag(8)
+        );
+        assert_eq!(
+            HighByte::<Basic>::try_untag(0xff << 56).unwrap_err(),
+            DecodeError::InvalidTag(0xff)
+        );
+        assert_eq!(
+            NanBoxing::<Basic>::try_untag(1.5f64.to_bits() as usize).unwrap_err(),
+            DecodeError::NotTagged
+        );
+        // A valid word apart from the sign bit
+        let signed = NanBoxing::<Basic>::QNAN | 0x1000 | 3;
+        assert!(NanBoxing::<Basic>::try_untag(signed).is_ok());
+        assert_eq!(
+            NanBoxing::<Basic>::try_untag(signed | 1 << 63).unwrap_err(),
+            DecodeError::NotTagged
+        );
+        assert_eq!(
+            Basic::try_from_raw(std::ptr::null(), 9).unwrap_err(),
+            DecodeError::InvalidTag(9)
+        );
+    }
+
+    #[test]
+    fn basic_round_trips() {
+        let x = X::new(13);
+        let ptr = &x as *const X<0> as *const u8;
+        for tag in 0..8 {
+            let basic = Basic::from_raw(ptr, tag);
+            assert_eq!((basic.tag(), basic.ptr()), (tag, ptr));
+        }
+    }
+
+    #[test]
+    fn nan_boxing_rejects_doubles() {
+        for double in [
+            0.0,
+            -0.0,
+            1.5,
+            -1.5,
+            f64::INFINITY,
+            f64::MIN_POSITIVE,
+            f64::MAX,
+        ] {
+            let x = NanBoxing::<Basic> {
+                data: double.to_bits() as usize as *const u8,
+                tag_type: PhantomData,
+            };
+            assert_eq!(x.tag(), 9, "{double}");
+        }
+    }
+
+    fn debug_eq_hash<S: TaggedPointer<Basic> + Copy + Eq + std::hash::Hash + std::fmt::Debug>(
+        name: &str,
+    ) {
+        let objects = [X::new(1), X::new(1)];
+        let a = S::from_raw(&objects[0] as *const X<0> as *const u8, 3);
+        let b = S::from_raw(&objects[1] as *const X<0> as *const u8, 3);
+        let c = S::from_raw(&objects[0] as *const X<0> as *const u8, 4);
+
+        let debug = format!("{a:?}");
+        let ptr = format!("{:?}", a.data());
+        assert_eq!(debug, format!("{name} {{ tag: 3, ptr: {ptr} }}"));
+
+        assert_eq!(a, S::new(a.untag()));
+        assert_ne!(a, b);
+        assert_ne!(a, c);
+        let set: HashSet<S> = [a, b, c, a].into_iter().collect();
+        assert_eq!(set.len(), 3);
+        assert!(set.contains(&S::new(Basic::T3(&objects[0]))));
+
+        let same_data = |x: Basic, y: Basic| unsafe {
+            (*(x.ptr() as *const X<0>)).data == (*(y.ptr() as *const X<0>)).data
+        };
+        let never = |_: Basic, _: Basic| false;
+        assert!(a.structural_eq(&b, same_data));
+        assert!(
+            a.structural_eq(&a, never),
+            "the same address is equal without calling pointee_eq"
+        );
+        assert!(!a.structural_eq(&b, never));
+        assert!(
+            !a.structural_eq(&c, same_data),
+            "different tags are never equal"
+        );
+    }
+
+    #[test]
+    fn schemes_debug_compare_and_hash() {
+        debug_eq_hash::<BaseLine>("BaseLine");
+        debug_eq_hash::<LowBits<Basic>>("LowBits");
+        debug_eq_hash::<LowByte<Basic>>("LowByte");
+        debug_eq_hash::<HighBits<Basic>>("HighBits");
+        debug_eq_hash::<HighByte<Basic>>("HighByte");
+        debug_eq_hash::<NanBoxing<Basic>>("NanBoxing");
+    }
+}
@@ -0,0 +1,43 @@
+// Compile c_version/roundtrip.c against c_version/tagging.h and the static
+// library cargo built next to this test, and run it, so the header and the
+// Rust side of src/ffi.rs can't drift apart.
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+#[cfg(unix)]
+fn c_round_trip() {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    // target/<profile>/deps/roundtrip-<hash> -> target/<profile>
+    let profile = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .to_path_buf();
+    let library = profile.join("libtagging.a");
+    assert!(library.exists(), "{} wasn't built", library.display());
+    let exe = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("roundtrip");
+
+    let compiler = cc::Build::new()
+        .target(env!("TARGET"))
+        .host(env!("TARGET"))
+        .opt_level(2)
+        .cargo_metadata(false)
+        .get_compiler();
+    let status = compiler
+        .to_command()
+        .arg(root.join("c_version/roundtrip.c"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm", "-o"])
+        .arg(&exe)
+        .status()
+        .expect("failed to run the C compiler");
+    assert!(status.success(), "compiling roundtrip.c failed");
+
+    let output = Command::new(&exe).output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "roundtrip failed:\n{stdout}");
+    assert_eq!(stdout.trim(), "all values round tripped");
+}